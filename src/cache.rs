@@ -0,0 +1,88 @@
+//! Responses that rarely change (like the list of fields), kept on disk between runs.
+//!
+//! The cache lives in `$JIRA_CACHE_DIR`, or `<cache dir>/jira`. It's best effort: entries that
+//! can't be read or written are simply fetched again.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn open() -> Cache {
+        let dir = match env::var_os("JIRA_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()
+                .map(|dir| dir.join("jira"))
+                .unwrap_or_else(|| env::temp_dir().join("jira-cache")),
+        };
+        Cache { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        let name = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// The entry called `name`, unless it's missing or older than `max_age`.
+    pub fn get<T: DeserializeOwned>(&self, name: &str, max_age: Duration) -> Option<T> {
+        let path = self.path(name);
+        let age = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if age > max_age {
+            return None;
+        }
+        let contents = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn put<T: Serialize>(&self, name: &str, value: &T) {
+        let contents = match serde_json::to_string(value) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+        if fs::create_dir_all(&self.dir).is_ok() {
+            let _ = fs::write(self.path(name), contents);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache {
+            dir: dir.path().join("cache"),
+        };
+        let day = Duration::from_secs(86_400);
+        assert_eq!(cache.get::<Vec<String>>("fields-example.net", day), None);
+
+        cache.put("fields-example.net", &vec!["Story Points"]);
+        assert_eq!(
+            cache.get::<Vec<String>>("fields-example.net", day),
+            Some(vec!["Story Points".to_owned()])
+        );
+        assert!(dir.path().join("cache/fields-example_net.json").is_file());
+    }
+}
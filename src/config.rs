@@ -0,0 +1,238 @@
+//! User configuration, read from `$JIRA_CONFIG` or `<config dir>/jira/config.toml`.
+//!
+//! ```toml
+//! subdomain = "mycompany"
+//! project = "ABC"
+//! columns = ["status", "key", "priority", "summary", "assignee", "story_points"]
+//!
+//! [projects.ABC]
+//! components = ["iOS", "Android"]
+//! filter = "labels != wontfix"
+//!
+//! [queries.triage]
+//! title = "Needs triage"
+//! jql = "project = {project} AND component in ({component}) AND status = Triage"
+//! ```
+//!
+//! Saved queries are run with `jira list <name>`. Their JQL can use these placeholders:
+//!
+//! - `{project}`: the current project
+//! - `{me}`: the current user
+//! - `{component}`: the components in the project's profile, comma separated
+//! - `{scope}`: the project, plus the components and filter in its profile
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::{Error, Result};
+
+/// Settings that apply to a single Jira project.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ProjectProfile {
+    /// Components that the built-in lists are scoped to, and that new issues are filed under.
+    pub components: Vec<String>,
+    /// Extra JQL that the built-in lists are scoped with, e.g. `labels = mobile`.
+    pub filter: Option<String>,
+}
+
+/// A named JQL query, run with `jira list <name>`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SavedQuery {
+    /// JQL, with `{project}`, `{me}`, `{component}` and `{scope}` placeholders.
+    pub jql: String,
+    /// The heading printed above the results.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Sort the results by type, status and key, with subtasks under their parents. Turn this
+    /// off to keep the order from the JQL's `ORDER BY` clause. `--sort` overrides either.
+    #[serde(default = "default_sort")]
+    pub sort: bool,
+    /// Other names the query can be run with.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+fn default_sort() -> bool {
+    true
+}
+
+impl SavedQuery {
+    fn builtin(jql: &str, title: &str, sort: bool, alias: &str) -> SavedQuery {
+        SavedQuery {
+            jql: jql.to_owned(),
+            title: Some(title.to_owned()),
+            sort,
+            aliases: vec![alias.to_owned()],
+        }
+    }
+}
+
+/// The queries available without any configuration. Saved queries with the same name
+/// replace these.
+fn builtin_queries() -> BTreeMap<String, SavedQuery> {
+    let mut queries = BTreeMap::new();
+    queries.insert(
+        "backlog".to_owned(),
+        SavedQuery::builtin(
+            "sprint is empty AND statusCategory != Done AND {scope}",
+            "Issues in the backlog",
+            true,
+            "b",
+        ),
+    );
+    queries.insert(
+        "epics".to_owned(),
+        SavedQuery::builtin(
+            "issuetype = Epic AND statusCategory != Done AND {scope} order by updated ASC",
+            "Epics",
+            false,
+            "e",
+        ),
+    );
+    queries.insert(
+        "me".to_owned(),
+        SavedQuery::builtin(
+            "assignee = {me} AND statusCategory != Done",
+            "Issues assigned to me",
+            true,
+            "m",
+        ),
+    );
+    queries.insert(
+        "sprint".to_owned(),
+        SavedQuery::builtin(
+            "sprint in openSprints () AND {scope}",
+            "Issues in the current sprint",
+            true,
+            "s",
+        ),
+    );
+    queries
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub subdomain: Option<String>,
+    pub base_url: Option<String>,
+    /// The project to use when `--project` isn't passed.
+    pub project: Option<String>,
+    pub projects: HashMap<String, ProjectProfile>,
+    pub queries: HashMap<String, SavedQuery>,
+    /// The columns of issue lists when `--columns` isn't passed, e.g.
+    /// `["key", "priority", "summary", "story_points"]`.
+    pub columns: Option<Vec<String>>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        match env::var_os("JIRA_CONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => dirs::config_dir().map(|dir| dir.join("jira").join("config.toml")),
+        }
+    }
+
+    /// Load the config file, treating a missing file as an empty config.
+    pub fn load() -> Result<Config> {
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents).map_err(|e| match e {
+                Error::Config { message, .. } => Error::Config {
+                    path: Some(path),
+                    message,
+                },
+                e => e,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Config> {
+        toml::from_str(contents).map_err(|e| Error::Config {
+            path: None,
+            message: e.to_string(),
+        })
+    }
+
+    /// The profile for `project`, or an empty one if it isn't configured.
+    pub fn profile(&self, project: &str) -> ProjectProfile {
+        self.projects.get(project).cloned().unwrap_or_default()
+    }
+
+    /// The built-in queries, overridden and extended by the saved ones, by name.
+    pub fn queries(&self) -> BTreeMap<String, SavedQuery> {
+        let mut queries = builtin_queries();
+        for (name, query) in &self.queries {
+            queries.insert(name.to_owned(), query.clone());
+        }
+        queries
+    }
+
+    /// Find a query by name or alias.
+    pub fn query(&self, name: &str) -> Option<SavedQuery> {
+        let queries = self.queries();
+        if let Some(query) = queries.get(name) {
+            return Some(query.clone());
+        }
+        queries
+            .values()
+            .find(|query| query.aliases.iter().any(|alias| alias == name))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles() {
+        let config = Config::parse(
+            r#"
+            project = "ABC"
+
+            [projects.ABC]
+            components = ["iOS", "Android"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.project.as_deref(), Some("ABC"));
+        assert_eq!(config.profile("ABC").components, vec!["iOS", "Android"]);
+        assert!(config.profile("XYZ").components.is_empty());
+    }
+
+    #[test]
+    fn test_saved_queries_extend_builtins() {
+        let config = Config::parse(
+            r#"
+            [queries.triage]
+            jql = "project = {project} AND status = Triage"
+            aliases = ["t"]
+
+            [queries.me]
+            jql = "assignee = {me}"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.query("t").unwrap().jql,
+            "project = {project} AND status = Triage"
+        );
+        assert!(config.query("triage").unwrap().sort);
+        assert_eq!(config.query("me").unwrap().jql, "assignee = {me}");
+        assert!(config.query("b").is_some());
+        assert!(config.query("nope").is_none());
+    }
+}
@@ -1,4 +1,4 @@
-//! Convert markdown to Atlassian's markup format
+//! Convert markdown to Atlassian's markup format, and back again
 //! Atlassian Document Format: https://developer.atlassian.com/cloud/jira/platform/apis/document/pub structure
 
 #![allow(dead_code)]
@@ -6,6 +6,10 @@
 use comrak::nodes::{AstNode, NodeValue};
 use comrak::{parse_document, Arena, ComrakOptions};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+
+use super::{Error, Result};
 
 fn append<T: Clone>(vec: Option<Vec<T>>, elem: T) -> Vec<T> {
     match vec {
@@ -30,8 +34,10 @@ pub enum TableLayout {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type", content = "attrs")]
+#[serde(try_from = "RawNodeType")]
 pub enum BlockNodeType {
     // Block
+    #[serde(rename = "blockquote")]
     BlockQuote,
     BulletList,
     CodeBlock {
@@ -43,11 +49,15 @@ pub enum BlockNodeType {
     MediaGroup,
     MediaSingle,
     OrderedList,
-    Panel,
+    #[serde(rename_all = "camelCase")]
+    Panel {
+        panel_type: String,
+    },
     Paragraph,
     Rule,
 
     // Child block
+    #[serde(rename_all = "camelCase")]
     Table {
         is_number_column_enabled: bool,
         layout: TableLayout,
@@ -55,7 +65,8 @@ pub enum BlockNodeType {
     ListItem,
     Media,
     TableCell {
-        background: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        background: Option<String>,
     },
     TableHeader,
     TableRow,
@@ -64,7 +75,9 @@ pub enum BlockNodeType {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "type", content = "attrs")]
+#[serde(try_from = "RawNodeType")]
 pub enum InlineNodeType {
+    #[serde(rename_all = "camelCase")]
     Emoji {
         id: Option<String>,
         short_name: String,
@@ -74,6 +87,7 @@ pub enum InlineNodeType {
     InlineCard {
         url: String,
     },
+    #[serde(rename_all = "camelCase")]
     Mention {
         id: String,
         text: Option<String>,
@@ -82,6 +96,98 @@ pub enum InlineNodeType {
     Text,
 }
 
+/// The `type` and `attrs` of a node as Jira sends them. Jira adds attributes we don't
+/// model (and leaves out ones we do) freely, so node types are read from this rather
+/// than with a derived `Deserialize`.
+#[derive(Deserialize)]
+struct RawNodeType {
+    #[serde(rename = "type")]
+    nodetype: String,
+    #[serde(default)]
+    attrs: Map<String, Value>,
+}
+
+impl RawNodeType {
+    fn attr_str(&self, name: &str) -> Option<String> {
+        self.attrs
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    }
+}
+
+impl TryFrom<RawNodeType> for BlockNodeType {
+    type Error = String;
+
+    fn try_from(raw: RawNodeType) -> std::result::Result<Self, Self::Error> {
+        Ok(match raw.nodetype.as_str() {
+            "blockquote" => BlockNodeType::BlockQuote,
+            "bulletList" => BlockNodeType::BulletList,
+            "codeBlock" => BlockNodeType::CodeBlock {
+                language: raw.attr_str("language"),
+            },
+            "heading" => BlockNodeType::Heading {
+                level: raw.attrs.get("level").and_then(|l| l.as_u64()).unwrap_or(1) as u32,
+            },
+            "mediaGroup" => BlockNodeType::MediaGroup,
+            "mediaSingle" => BlockNodeType::MediaSingle,
+            "orderedList" => BlockNodeType::OrderedList,
+            "panel" => BlockNodeType::Panel {
+                panel_type: raw
+                    .attr_str("panelType")
+                    .unwrap_or_else(|| "info".to_owned()),
+            },
+            "paragraph" => BlockNodeType::Paragraph,
+            "rule" => BlockNodeType::Rule,
+            "table" => BlockNodeType::Table {
+                is_number_column_enabled: raw
+                    .attrs
+                    .get("isNumberColumnEnabled")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                layout: match raw.attr_str("layout").as_deref() {
+                    Some("full-width") => TableLayout::FullWidth,
+                    Some("wide") => TableLayout::Wide,
+                    _ => TableLayout::Default,
+                },
+            },
+            "listItem" => BlockNodeType::ListItem,
+            "media" => BlockNodeType::Media,
+            "tableCell" => BlockNodeType::TableCell {
+                background: raw.attr_str("background"),
+            },
+            "tableHeader" => BlockNodeType::TableHeader,
+            "tableRow" => BlockNodeType::TableRow,
+            other => return Err(format!("unknown block node type '{}'", other)),
+        })
+    }
+}
+
+impl TryFrom<RawNodeType> for InlineNodeType {
+    type Error = String;
+
+    fn try_from(raw: RawNodeType) -> std::result::Result<Self, Self::Error> {
+        Ok(match raw.nodetype.as_str() {
+            "emoji" => InlineNodeType::Emoji {
+                id: raw.attr_str("id"),
+                short_name: raw.attr_str("shortName").unwrap_or_default(),
+                text: raw.attr_str("text"),
+            },
+            "hardBreak" => InlineNodeType::HardBreak,
+            "inlineCard" => InlineNodeType::InlineCard {
+                url: raw.attr_str("url").unwrap_or_default(),
+            },
+            "mention" => InlineNodeType::Mention {
+                id: raw.attr_str("id").unwrap_or_default(),
+                text: raw.attr_str("text"),
+                user_type: raw.attr_str("userType"),
+            },
+            "text" => InlineNodeType::Text,
+            other => return Err(format!("unknown inline node type '{}'", other)),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum SubsupType {
@@ -119,6 +225,7 @@ pub enum Node {
         #[serde(rename = "type")]
         #[serde(flatten)]
         nodetype: BlockNodeType,
+        #[serde(default)]
         content: Vec<Node>,
     },
     InlineNode {
@@ -136,221 +243,934 @@ pub enum Node {
         doctype: String,
         content: Vec<Node>,
     },
+    /// Anything we don't model (status lozenges, dates, expands...), kept as-is so that it
+    /// survives a round trip. Only its text is displayed.
+    Unknown(Value),
+}
+
+fn unsupported(feature: &str) -> Result<Vec<Node>> {
+    Err(Error::UnsupportedMarkdown {
+        feature: feature.to_owned(),
+    })
+}
+
+fn bytes_to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
 }
 
-// TODO: Return a `Result` instead of panic-ing on unsupported CommonMark features
-fn convert_node_markdown_to_adf<'a>(node: &'a AstNode<'a>, marks: Option<Vec<Mark>>) -> Node {
+fn convert_children_markdown_to_adf<'a>(
+    node: &'a AstNode<'a>,
+    marks: Option<Vec<Mark>>,
+) -> Result<Vec<Node>> {
+    let mut content = Vec::new();
+    for child in node.children() {
+        content.append(&mut convert_node_markdown_to_adf(child, marks.clone())?);
+    }
+    Ok(content)
+}
+
+fn convert_block_markdown_to_adf<'a>(
+    node: &'a AstNode<'a>,
+    nodetype: BlockNodeType,
+) -> Result<Vec<Node>> {
+    Ok(vec![Node::BlockNode {
+        nodetype,
+        content: convert_children_markdown_to_adf(node, None)?,
+    }])
+}
+
+/// Convert a single CommonMark node. Inline formatting (emphasis, links, etc.) is
+/// flattened into marks on its children, so this can yield any number of ADF nodes.
+fn convert_node_markdown_to_adf<'a>(
+    node: &'a AstNode<'a>,
+    marks: Option<Vec<Mark>>,
+) -> Result<Vec<Node>> {
     match &node.data.borrow().value {
-        NodeValue::Document => Node::Root {
+        NodeValue::Document => Ok(vec![Node::Root {
             version: 1,
             doctype: "doc".to_owned(),
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::BlockQuote => Node::BlockNode {
-            nodetype: BlockNodeType::BlockQuote,
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
+            content: convert_children_markdown_to_adf(node, None)?,
+        }]),
+        NodeValue::BlockQuote => convert_block_markdown_to_adf(node, BlockNodeType::BlockQuote),
         NodeValue::List(list) => match list.list_type {
-            comrak::nodes::ListType::Bullet => Node::BlockNode {
-                nodetype: BlockNodeType::BulletList,
-                content: node
-                    .children()
-                    .map(|c| convert_node_markdown_to_adf(c, None))
-                    .collect(),
-            },
-            comrak::nodes::ListType::Ordered => Node::BlockNode {
-                nodetype: BlockNodeType::OrderedList,
-                content: node
-                    .children()
-                    .map(|c| convert_node_markdown_to_adf(c, None))
-                    .collect(),
-            },
-        },
-        NodeValue::Item(_) => Node::BlockNode {
-            nodetype: BlockNodeType::ListItem,
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
+            comrak::nodes::ListType::Bullet => {
+                convert_block_markdown_to_adf(node, BlockNodeType::BulletList)
+            }
+            comrak::nodes::ListType::Ordered => {
+                convert_block_markdown_to_adf(node, BlockNodeType::OrderedList)
+            }
         },
-        NodeValue::DescriptionList => {
-            panic!("Definition lists are unsupported!");
+        NodeValue::Item(_) => convert_block_markdown_to_adf(node, BlockNodeType::ListItem),
+        NodeValue::DescriptionList
+        | NodeValue::DescriptionItem(_)
+        | NodeValue::DescriptionTerm
+        | NodeValue::DescriptionDetails => unsupported("definition lists"),
+        NodeValue::CodeBlock(code) => {
+            let language = bytes_to_string(&code.info);
+            let literal = bytes_to_string(&code.literal);
+            let literal = literal.trim_end_matches('\n');
+            Ok(vec![Node::BlockNode {
+                nodetype: BlockNodeType::CodeBlock {
+                    language: language.split_whitespace().next().map(String::from),
+                },
+                content: if literal.is_empty() {
+                    vec![]
+                } else {
+                    vec![Node::InlineNode {
+                        nodetype: InlineNodeType::Text,
+                        text: Some(literal.to_owned()),
+                        marks: None,
+                    }]
+                },
+            }])
         }
-        NodeValue::DescriptionItem(_) => {
-            panic!("Definition lists are unsupported!");
-        }
-        NodeValue::DescriptionTerm => {
-            panic!("Definition lists are unsupported!");
-        }
-        NodeValue::DescriptionDetails => {
-            panic!("Definition lists are unsupported!");
-        }
-        NodeValue::CodeBlock(code) => Node::BlockNode {
-            nodetype: BlockNodeType::CodeBlock {
-                language: std::str::from_utf8(&code.info).ok().map(String::from),
-            },
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::HtmlBlock(_) => {
-            panic!("HTML blocks are unsupported!");
-        }
-        NodeValue::Paragraph => Node::BlockNode {
-            nodetype: BlockNodeType::Paragraph,
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::Heading(heading) => Node::BlockNode {
-            nodetype: BlockNodeType::Heading {
+        NodeValue::HtmlBlock(_) => unsupported("HTML blocks"),
+        NodeValue::Paragraph => convert_block_markdown_to_adf(node, BlockNodeType::Paragraph),
+        NodeValue::Heading(heading) => convert_block_markdown_to_adf(
+            node,
+            BlockNodeType::Heading {
                 level: heading.level,
             },
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::ThematicBreak => Node::BlockNode {
+        ),
+        NodeValue::ThematicBreak => Ok(vec![Node::BlockNode {
             nodetype: BlockNodeType::Rule,
             content: vec![],
-        },
-        NodeValue::FootnoteDefinition(_) => {
-            panic!("Footnotes aren't supported!");
-        }
-        NodeValue::Table(_) => Node::BlockNode {
-            nodetype: BlockNodeType::Table {
+        }]),
+        NodeValue::FootnoteDefinition(_) => unsupported("footnotes"),
+        NodeValue::Table(_) => convert_block_markdown_to_adf(
+            node,
+            BlockNodeType::Table {
                 is_number_column_enabled: false,
                 layout: TableLayout::Default,
             },
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::TableRow(_) => Node::BlockNode {
-            nodetype: BlockNodeType::TableRow,
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::TableCell => Node::BlockNode {
-            nodetype: BlockNodeType::TableCell {
-                background: "#ffffff".to_owned(),
-            },
-            content: node
-                .children()
-                .map(|c| convert_node_markdown_to_adf(c, None))
-                .collect(),
-        },
-        NodeValue::Text(text) => Node::InlineNode {
+        ),
+        NodeValue::TableRow(_) => convert_block_markdown_to_adf(node, BlockNodeType::TableRow),
+        // Cells in a Markdown table's first row are its headers. ADF cells hold blocks, so
+        // their text goes in a paragraph.
+        NodeValue::TableCell => {
+            let nodetype = match node.parent().map(|row| row.data.borrow().value.clone()) {
+                Some(NodeValue::TableRow(true)) => BlockNodeType::TableHeader,
+                _ => BlockNodeType::TableCell {
+                    background: Some("#ffffff".to_owned()),
+                },
+            };
+            Ok(vec![Node::BlockNode {
+                nodetype,
+                content: convert_block_markdown_to_adf(node, BlockNodeType::Paragraph)?,
+            }])
+        }
+        NodeValue::Text(text) => Ok(vec![Node::InlineNode {
             nodetype: InlineNodeType::Text,
-            text: Some(
-                std::str::from_utf8(&text[..])
-                    .expect("Invalid UTF-8!")
-                    .to_owned(),
-            ),
+            text: Some(bytes_to_string(text)),
             marks,
-        },
-        NodeValue::TaskItem(_) => {
-            panic!("Task lists not supported!");
-        }
-        NodeValue::SoftBreak => Node::InlineNode {
+        }]),
+        NodeValue::TaskItem(_) => unsupported("task lists"),
+        NodeValue::SoftBreak => Ok(vec![Node::InlineNode {
             nodetype: InlineNodeType::Text,
             text: Some(" ".to_owned()),
             marks: None,
-        },
-        NodeValue::LineBreak => Node::InlineNode {
+        }]),
+        NodeValue::LineBreak => Ok(vec![Node::InlineNode {
             nodetype: InlineNodeType::HardBreak,
             marks: None,
             text: None,
-        },
-        NodeValue::Code(text) => Node::InlineNode {
+        }]),
+        NodeValue::Code(text) => Ok(vec![Node::InlineNode {
             nodetype: InlineNodeType::Text,
-            text: Some(
-                std::str::from_utf8(&text[..])
-                    .expect("Invalid UTF-8!")
-                    .to_owned(),
-            ),
+            text: Some(bytes_to_string(text)),
             marks: Some(vec![Mark::Code]),
-        },
-        NodeValue::HtmlInline(_) => {
-            panic!("Raw HTML not supported!");
-        }
-        NodeValue::Emph => {
-            let marks = append(marks, Mark::Em);
-            convert_node_markdown_to_adf(
-                node.first_child().expect("Nothing to emphasize!"),
-                Some(marks),
-            )
-        }
+        }]),
+        NodeValue::HtmlInline(_) => unsupported("raw HTML"),
+        NodeValue::Emph => convert_children_markdown_to_adf(node, Some(append(marks, Mark::Em))),
         NodeValue::Strong => {
-            let marks = append(marks, Mark::Strong);
-            convert_node_markdown_to_adf(
-                node.first_child().expect("Nothing to embolden!"),
-                Some(marks),
-            )
+            convert_children_markdown_to_adf(node, Some(append(marks, Mark::Strong)))
         }
         NodeValue::Strikethrough => {
-            let marks = append(marks, Mark::Strike);
-            convert_node_markdown_to_adf(
-                node.first_child().expect("Nothing to strikethrough!"),
-                Some(marks),
-            )
+            convert_children_markdown_to_adf(node, Some(append(marks, Mark::Strike)))
         }
         NodeValue::Superscript => {
             let mark = Mark::Subsup {
                 subsuptype: SubsupType::Sup,
             };
-            let marks = append(marks, mark);
-            convert_node_markdown_to_adf(
-                node.first_child().expect("Nothing to superscript!"),
-                Some(marks),
-            )
+            convert_children_markdown_to_adf(node, Some(append(marks, mark)))
         }
         NodeValue::Link(link) => {
             let mark = Mark::Link {
-                href: std::str::from_utf8(&link.url[..])
-                    .expect("Invalid UTF-8!")
-                    .to_owned(),
-                title: Some(
-                    std::str::from_utf8(&link.title[..])
-                        .expect("Invalid UTF-8!")
-                        .to_owned(),
-                ),
+                href: bytes_to_string(&link.url),
+                title: Some(bytes_to_string(&link.title)),
             };
+            convert_children_markdown_to_adf(node, Some(append(marks, mark)))
+        }
+        // Images can't be embedded without uploading them first, so keep them as links
+        NodeValue::Image(link) => {
+            let href = bytes_to_string(&link.url);
+            let marks = append(
+                marks,
+                Mark::Link {
+                    href: href.to_owned(),
+                    title: Some(bytes_to_string(&link.title)),
+                },
+            );
+            let content = convert_children_markdown_to_adf(node, Some(marks.clone()))?;
+            if content.is_empty() {
+                Ok(vec![Node::InlineNode {
+                    nodetype: InlineNodeType::Text,
+                    text: Some(href),
+                    marks: Some(marks),
+                }])
+            } else {
+                Ok(content)
+            }
+        }
+        NodeValue::FootnoteReference(_) => unsupported("footnotes"),
+        NodeValue::FrontMatter(_) => unsupported("front matter"),
+    }
+}
+
+pub fn markdown_to_adf(text: &str) -> Result<Node> {
+    let arena = Arena::new();
+
+    // Enable the extensions that `adf_to_markdown` can produce
+    let mut options = ComrakOptions::default();
+    options.extension.table = true;
+    options.extension.strikethrough = true;
 
-            let marks = append(marks, mark);
-            convert_node_markdown_to_adf(
-                node.first_child().expect("Nothing to superscript!"),
-                Some(marks),
+    let root = parse_document(&arena, text, &options);
+
+    let mut nodes = convert_node_markdown_to_adf(root, None)?;
+    Ok(nodes.remove(0))
+}
+
+// Escape the characters that would otherwise turn plain text into formatting. Markers that
+// only count at the start of a line (headings, lists, quotes) are escaped there: at the start
+// of `text` if it begins a line, and after any newline in it.
+fn escape_markdown(text: &str, at_line_start: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            escaped.push('\n');
+        }
+        let content = line.trim_start_matches(' ');
+        escaped.push_str(&line[..line.len() - content.len()]);
+
+        let mut rest = content;
+        if at_line_start || i > 0 {
+            let digits = content.len()
+                - content
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            match content[digits..].chars().next() {
+                // An ordered list marker, e.g. `1.` or `1)`
+                Some('.') | Some(')') if digits > 0 => {
+                    escaped.push_str(&content[..digits]);
+                    escaped.push('\\');
+                    rest = &content[digits..];
+                }
+                Some('#') | Some('-') | Some('+') | Some('>') | Some('=') if digits == 0 => {
+                    escaped.push('\\')
+                }
+                _ => {}
+            }
+        }
+
+        for c in rest.chars() {
+            if let '\\' | '*' | '_' | '`' | '[' | ']' | '~' | '|' | '<' = c {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0)
+}
+
+// Wrap text in a code span, with a fence longer than any run of backticks inside it.
+// Padding keeps backticks at the ends out of the fence, and stops CommonMark from
+// stripping spaces that are really part of the code.
+fn code_span(text: &str) -> String {
+    let fence = "`".repeat(longest_backtick_run(text) + 1);
+    let pad = text.starts_with('`')
+        || text.ends_with('`')
+        || (text.starts_with(' ') && text.ends_with(' ') && !text.trim().is_empty());
+    if pad {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
+}
+
+// Indent every line but the first, which follows a list marker.
+fn indent_continuation(text: &str, width: usize) -> String {
+    let indent = " ".repeat(width);
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() {
+                line.to_owned()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The text inside a node we don't model, e.g. the label of a status lozenge.
+pub fn unknown_node_text(value: &Value) -> String {
+    let mut text = String::new();
+    if let Some(t) = value.get("text").and_then(|t| t.as_str()) {
+        text.push_str(t);
+    } else if let Some(t) = value.pointer("/attrs/text").and_then(|t| t.as_str()) {
+        text.push_str(t);
+    }
+    if let Some(content) = value.get("content").and_then(|c| c.as_array()) {
+        for child in content {
+            text.push_str(&unknown_node_text(child));
+        }
+    }
+    text
+}
+
+fn inline_adf_to_markdown(node: &Node) -> String {
+    inline_to_markdown(node, false)
+}
+
+// An inline node, which starts a line if `at_line_start` is set.
+fn inline_to_markdown(node: &Node, at_line_start: bool) -> String {
+    match node {
+        Node::InlineNode {
+            nodetype: InlineNodeType::Text,
+            text,
+            marks,
+        } => {
+            let text = text.as_deref().unwrap_or_default();
+            let marks = marks.as_deref().unwrap_or_default();
+
+            let mut text = if marks.iter().any(|m| matches!(m, Mark::Code)) {
+                code_span(text)
+            } else {
+                escape_markdown(text, at_line_start)
+            };
+            for mark in marks {
+                text = match mark {
+                    Mark::Strike => format!("~~{}~~", text),
+                    Mark::Em => format!("*{}*", text),
+                    Mark::Strong => format!("**{}**", text),
+                    _ => text,
+                };
+            }
+            // Links go outermost, so that formatting ends up inside the link text
+            for mark in marks {
+                if let Mark::Link { href, .. } = mark {
+                    text = format!("[{}]({})", text, href);
+                }
+            }
+            text
+        }
+        Node::InlineNode {
+            nodetype: InlineNodeType::HardBreak,
+            ..
+        } => "\\\n".to_owned(),
+        Node::InlineNode {
+            nodetype: InlineNodeType::Emoji {
+                short_name, text, ..
+            },
+            ..
+        } => text.to_owned().unwrap_or_else(|| short_name.to_owned()),
+        Node::InlineNode {
+            nodetype: InlineNodeType::Mention { id, text, .. },
+            ..
+        } => text.to_owned().unwrap_or_else(|| format!("@{}", id)),
+        Node::InlineNode {
+            nodetype: InlineNodeType::InlineCard { url },
+            ..
+        } => format!("<{}>", url),
+        Node::Unknown(value) => unknown_node_text(value),
+        node => block_adf_to_markdown(node),
+    }
+}
+
+fn inline_content_to_markdown(content: &[Node]) -> String {
+    let mut markdown = String::new();
+    for node in content {
+        let at_line_start = markdown.is_empty() || markdown.ends_with('\n');
+        markdown.push_str(&inline_to_markdown(node, at_line_start));
+    }
+    markdown
+}
+
+// Escape the pipes in a table cell that aren't already, including those in code spans.
+fn escape_cell_pipes(cell: &str) -> String {
+    let mut escaped = String::with_capacity(cell.len());
+    let mut backslashes = 0;
+    for c in cell.chars() {
+        if c == '|' && backslashes % 2 == 0 {
+            escaped.push('\\');
+        }
+        backslashes = if c == '\\' { backslashes + 1 } else { 0 };
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn blocks_to_markdown(content: &[Node]) -> String {
+    content
+        .iter()
+        .map(block_adf_to_markdown)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// The blocks in a list item; nested lists hug the paragraph they follow.
+fn list_item_to_markdown(item: &Node) -> String {
+    let content = match item {
+        Node::BlockNode { content, .. } => content,
+        node => return block_adf_to_markdown(node),
+    };
+
+    let mut markdown = String::new();
+    for (i, child) in content.iter().enumerate() {
+        if i > 0 {
+            markdown.push_str(match child {
+                Node::BlockNode {
+                    nodetype: BlockNodeType::BulletList,
+                    ..
+                }
+                | Node::BlockNode {
+                    nodetype: BlockNodeType::OrderedList,
+                    ..
+                } => "\n",
+                _ => "\n\n",
+            });
+        }
+        markdown.push_str(&block_adf_to_markdown(child));
+    }
+    markdown
+}
+
+fn table_to_markdown(rows: &[Node]) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| match row {
+            Node::BlockNode { content, .. } => content
+                .iter()
+                .map(|cell| {
+                    let cell = match cell {
+                        Node::BlockNode { content, .. } => content
+                            .iter()
+                            .map(|c| match c {
+                                Node::BlockNode { content, .. } => {
+                                    inline_content_to_markdown(content)
+                                }
+                                c => inline_adf_to_markdown(c),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        cell => inline_adf_to_markdown(cell),
+                    };
+                    escape_cell_pipes(&cell).replace('\n', " ")
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        })
+        .collect::<Vec<_>>();
+
+    let columns = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let mut cells = row.clone();
+        cells.resize(columns, String::new());
+        lines.push(format!("| {} |", cells.join(" | ")));
+        // Markdown tables need a header, so the first row always becomes one
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+    lines.join("\n")
+}
+
+fn block_adf_to_markdown(node: &Node) -> String {
+    let (nodetype, content) = match node {
+        Node::Root { content, .. } => return blocks_to_markdown(content),
+        Node::BlockNode { nodetype, content } => (nodetype, content),
+        Node::Unknown(value) => return unknown_node_text(value),
+        inline => return inline_adf_to_markdown(inline),
+    };
+
+    match nodetype {
+        BlockNodeType::Paragraph => inline_content_to_markdown(content),
+        BlockNodeType::Heading { level } => format!(
+            "{} {}",
+            "#".repeat(*level as usize),
+            inline_content_to_markdown(content)
+        ),
+        BlockNodeType::BulletList => content
+            .iter()
+            .map(|item| format!("- {}", indent_continuation(&list_item_to_markdown(item), 2)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        BlockNodeType::OrderedList => content
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = format!("{}. ", i + 1);
+                let item = indent_continuation(&list_item_to_markdown(item), marker.len());
+                format!("{}{}", marker, item)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        BlockNodeType::CodeBlock { language } => {
+            let code = content
+                .iter()
+                .map(|c| match c {
+                    Node::InlineNode { text, .. } => text.to_owned().unwrap_or_default(),
+                    c => inline_adf_to_markdown(c),
+                })
+                .collect::<String>();
+            // The fence has to be longer than any run of backticks in the code, or a line
+            // of it could close the block early
+            let fence = "`".repeat(std::cmp::max(3, longest_backtick_run(&code) + 1));
+            format!(
+                "{}{}\n{}\n{}",
+                fence,
+                language.as_deref().unwrap_or_default(),
+                code,
+                fence
             )
         }
-        NodeValue::Image(_) => {
-            panic!("Images aren't supported, sorry!");
+        BlockNodeType::BlockQuote | BlockNodeType::Panel { .. } => blocks_to_markdown(content)
+            .lines()
+            .map(|line| {
+                if line.is_empty() {
+                    ">".to_owned()
+                } else {
+                    format!("> {}", line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        BlockNodeType::Rule => "---".to_owned(),
+        BlockNodeType::Table { .. } => table_to_markdown(content),
+        BlockNodeType::ListItem => list_item_to_markdown(node),
+        BlockNodeType::TableRow | BlockNodeType::TableCell { .. } | BlockNodeType::TableHeader => {
+            blocks_to_markdown(content)
+        }
+        // Attachments can't be shown inline
+        BlockNodeType::MediaGroup | BlockNodeType::MediaSingle | BlockNodeType::Media => {
+            String::new()
+        }
+    }
+}
+
+/// Convert an ADF document (or any node in one) to Markdown. Nodes that Markdown can't
+/// express (panels, mentions, colours...) are approximated.
+pub fn adf_to_markdown(node: &Node) -> String {
+    block_adf_to_markdown(node)
+}
+
+/// What replacing `node` with its Markdown would lose, e.g. `["attachments", "mentions"]`.
+/// Empty if the Markdown says everything the document does.
+pub fn markdown_losses(node: &Node) -> Vec<String> {
+    fn add(losses: &mut Vec<String>, loss: &str) {
+        if !losses.iter().any(|l| l == loss) {
+            losses.push(loss.to_owned());
         }
-        NodeValue::FootnoteReference(_) => {
-            panic!("Footnotes aren't supported!");
+    }
+
+    // Markdown tables have exactly one row of headers, at the top, and no colours
+    fn collect_table(rows: &[Node], losses: &mut Vec<String>) {
+        for (i, row) in rows.iter().enumerate() {
+            let cells = match row {
+                Node::BlockNode { content, .. } => content,
+                row => {
+                    collect(row, losses);
+                    continue;
+                }
+            };
+            for cell in cells {
+                match cell {
+                    Node::BlockNode {
+                        nodetype: BlockNodeType::TableHeader,
+                        ..
+                    } if i > 0 => add(losses, "table header columns"),
+                    Node::BlockNode {
+                        nodetype: BlockNodeType::TableCell { background },
+                        ..
+                    } => {
+                        if i == 0 {
+                            add(losses, "tables without a header row");
+                        }
+                        if let Some(background) = background {
+                            if !background.eq_ignore_ascii_case("#ffffff") {
+                                add(losses, "cell colours");
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                collect(cell, losses);
+            }
         }
-        NodeValue::FrontMatter(_) => {
-            panic!("Front matter isn't supported!");
+    }
+
+    fn collect(node: &Node, losses: &mut Vec<String>) {
+        let content = match node {
+            Node::Root { content, .. } => content,
+            Node::BlockNode {
+                nodetype: BlockNodeType::Table { .. },
+                content,
+            } => return collect_table(content, losses),
+            Node::BlockNode { nodetype, content } => {
+                match nodetype {
+                    BlockNodeType::Media
+                    | BlockNodeType::MediaGroup
+                    | BlockNodeType::MediaSingle => return add(losses, "attachments"),
+                    BlockNodeType::Panel { .. } => add(losses, "panels"),
+                    _ => {}
+                }
+                content
+            }
+            Node::InlineNode {
+                nodetype, marks, ..
+            } => {
+                match nodetype {
+                    InlineNodeType::Mention { .. } => add(losses, "mentions"),
+                    InlineNodeType::Emoji { .. } => add(losses, "emoji"),
+                    InlineNodeType::InlineCard { .. } => add(losses, "smart links"),
+                    InlineNodeType::Text | InlineNodeType::HardBreak => {}
+                }
+                for mark in marks.iter().flatten() {
+                    match mark {
+                        Mark::Underline => add(losses, "underlines"),
+                        Mark::TextColor { .. } => add(losses, "text colours"),
+                        Mark::Subsup { .. } => add(losses, "subscripts and superscripts"),
+                        Mark::Code | Mark::Em | Mark::Link { .. } | Mark::Strike | Mark::Strong => {
+                        }
+                    }
+                }
+                return;
+            }
+            Node::Unknown(value) => {
+                return match value.get("type").and_then(|t| t.as_str()) {
+                    Some(nodetype) => add(losses, &format!("{} nodes", nodetype)),
+                    None => add(losses, "unknown nodes"),
+                };
+            }
+        };
+        for child in content {
+            collect(child, losses);
         }
     }
+
+    let mut losses = Vec::new();
+    collect(node, &mut losses);
+    losses
 }
 
-pub fn markdown_to_adf(text: &str) -> Node {
-    let arena = Arena::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_markdown() {
+        match markdown_to_adf("<details>\n\nlogs\n\n</details>") {
+            Err(Error::UnsupportedMarkdown { feature }) => assert_eq!(feature, "HTML blocks"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_images_become_links() {
+        let doc = markdown_to_adf("![screenshot](https://example.com/a.png) ![](b.png)").unwrap();
+        assert_eq!(
+            adf_to_markdown(&doc),
+            "[screenshot](https://example.com/a.png) [b.png](b.png)"
+        );
+    }
+
+    #[test]
+    fn test_emphasis_keeps_every_child() {
+        let doc = markdown_to_adf("*one `two` three*").unwrap();
+        let paragraph = match doc {
+            Node::Root { mut content, .. } => content.remove(0),
+            node => panic!("Unexpected node: {:?}", node),
+        };
+        match paragraph {
+            Node::BlockNode { content, .. } => assert_eq!(content.len(), 3),
+            node => panic!("Unexpected node: {:?}", node),
+        }
+    }
 
-    let root = parse_document(&arena, text, &ComrakOptions::default());
+    #[test]
+    fn test_code_block_keeps_its_code() {
+        let doc = markdown_to_adf("```rust\nfn main() {}\n```").unwrap();
+        assert_eq!(adf_to_markdown(&doc), "```rust\nfn main() {}\n```");
+    }
+
+    #[test]
+    fn test_code_block_with_fences() {
+        let code = "Steps:\n```sh\nmake\n```\nand ```` too";
+        let doc = Node::Root {
+            version: 1,
+            doctype: "doc".to_owned(),
+            content: vec![Node::BlockNode {
+                nodetype: BlockNodeType::CodeBlock {
+                    language: Some("markdown".to_owned()),
+                },
+                content: vec![Node::InlineNode {
+                    nodetype: InlineNodeType::Text,
+                    text: Some(code.to_owned()),
+                    marks: None,
+                }],
+            }],
+        };
+        let markdown = adf_to_markdown(&doc);
+        assert!(markdown.starts_with("`````markdown\n"));
+        assert_eq!(
+            serde_json::to_value(&markdown_to_adf(&markdown).unwrap()).unwrap(),
+            serde_json::to_value(&doc).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_code_with_backticks() {
+        for code in &["a`b", "`", "``x", " a ", "x`` ` y"] {
+            let doc = Node::Root {
+                version: 1,
+                doctype: "doc".to_owned(),
+                content: vec![Node::BlockNode {
+                    nodetype: BlockNodeType::Paragraph,
+                    content: vec![Node::InlineNode {
+                        nodetype: InlineNodeType::Text,
+                        text: Some(code.to_string()),
+                        marks: Some(vec![Mark::Code]),
+                    }],
+                }],
+            };
+            let markdown = adf_to_markdown(&doc);
+            let round_trip = markdown_to_adf(&markdown).unwrap();
+            assert_eq!(
+                serde_json::to_value(&round_trip).unwrap(),
+                serde_json::to_value(&doc).unwrap(),
+                "{:?} became {:?}",
+                code,
+                markdown
+            );
+        }
+        assert_eq!(code_span("a`b"), "``a`b``");
+        assert_eq!(code_span("`"), "`` ` ``");
+    }
 
-    convert_node_markdown_to_adf(root, None)
+    #[test]
+    fn test_plain_text_round_trip() {
+        let paragraphs = [
+            "# not a heading [x](y) ~~z~~ 1. item",
+            "- also not a list",
+            "+ nor this",
+            "> or a quote",
+            "1) first",
+            "2020. was a year",
+            "a <b> | c ~ d ] e",
+            "snake_case *stars* `ticks` \\ backslash",
+        ];
+        let paragraph = |text: &str| Node::BlockNode {
+            nodetype: BlockNodeType::Paragraph,
+            content: vec![Node::InlineNode {
+                nodetype: InlineNodeType::Text,
+                text: Some(text.to_owned()),
+                marks: None,
+            }],
+        };
+        for text in &paragraphs {
+            let doc = Node::Root {
+                version: 1,
+                doctype: "doc".to_owned(),
+                content: vec![paragraph(text)],
+            };
+            let markdown = adf_to_markdown(&doc);
+            let round_trip = markdown_to_adf(&markdown).unwrap();
+            assert_eq!(
+                serde_json::to_value(&round_trip).unwrap(),
+                serde_json::to_value(&doc).unwrap(),
+                "{:?} became {:?}",
+                text,
+                markdown
+            );
+        }
+        assert_eq!(
+            escape_markdown("one\n- two\n3. three", false),
+            "one\n\\- two\n3\\. three"
+        );
+    }
+
+    #[test]
+    fn test_table_cells_escape_pipes_once() {
+        let markdown = "| a \\| b | `c\\|d` |\n| --- | --- |\n| 1 | 2 |";
+        let doc = markdown_to_adf(markdown).unwrap();
+        assert_eq!(adf_to_markdown(&doc), markdown);
+    }
+
+    // A document with `content`, as Jira would send it.
+    fn jira_doc(content: &str) -> Node {
+        serde_json::from_str(&format!(
+            r#"{{"version": 1, "type": "doc", "content": [{}]}}"#,
+            content
+        ))
+        .unwrap()
+    }
+
+    fn round_trips(doc: &Node) -> bool {
+        let round_trip = markdown_to_adf(&adf_to_markdown(doc)).unwrap();
+        serde_json::to_value(&round_trip).unwrap() == serde_json::to_value(doc).unwrap()
+    }
+
+    #[test]
+    fn test_markdown_losses() {
+        let doc = jira_doc(
+            r#"{"type": "panel", "attrs": {"panelType": "info"}, "content": [
+                    {"type": "paragraph", "content": [
+                        {"type": "mention", "attrs": {"id": "abc", "text": "@Jane"}},
+                        {"type": "status", "attrs": {"text": "BLOCKED"}}
+                    ]}
+                ]},
+                {"type": "mediaSingle", "content": [{"type": "media", "attrs": {"id": "1"}}]},
+                {"type": "paragraph", "content": [{"type": "mention", "attrs": {"id": "def"}}]}"#,
+        );
+        assert_eq!(
+            markdown_losses(&doc),
+            vec!["panels", "mentions", "status nodes", "attachments"]
+        );
+        assert!(!round_trips(&doc));
+    }
+
+    #[test]
+    fn test_markdown_losses_in_text() {
+        let text = |attrs: &str| {
+            jira_doc(&format!(
+                r#"{{"type": "paragraph", "content": [{}]}}"#,
+                attrs
+            ))
+        };
+        let cases = [
+            (
+                r#"{"type": "text", "text": "a", "marks": [{"type": "underline"}]}"#,
+                "underlines",
+            ),
+            (
+                r##"{"type": "text", "text": "a", "marks": [{"type": "textColor", "attrs": {"color": "#ff0000"}}]}"##,
+                "text colours",
+            ),
+            (
+                r#"{"type": "text", "text": "2", "marks": [{"type": "subsup", "attrs": {"type": "sup"}}]}"#,
+                "subscripts and superscripts",
+            ),
+            (
+                r#"{"type": "emoji", "attrs": {"shortName": ":fire:", "text": "🔥"}}"#,
+                "emoji",
+            ),
+            (
+                r#"{"type": "inlineCard", "attrs": {"url": "https://example.com"}}"#,
+                "smart links",
+            ),
+        ];
+        for (node, loss) in &cases {
+            let doc = text(node);
+            assert_eq!(markdown_losses(&doc), vec![*loss]);
+            assert!(!round_trips(&doc), "{} survived", loss);
+        }
+
+        let doc = text(r#"{"type": "text", "text": "fine", "marks": [{"type": "strong"}]}"#);
+        assert!(markdown_losses(&doc).is_empty());
+        assert!(round_trips(&doc));
+    }
+
+    #[test]
+    fn test_markdown_losses_in_tables() {
+        let table = |rows: &str| {
+            jira_doc(&format!(
+                r#"{{"type": "table", "attrs": {{"isNumberColumnEnabled": false, "layout": "default"}}, "content": [{}]}}"#,
+                rows
+            ))
+        };
+        let cell = |nodetype: &str, attrs: &str| {
+            format!(
+                r#"{{"type": "{}", "attrs": {{{}}}, "content": [{{"type": "paragraph", "content": [{{"type": "text", "text": "x"}}]}}]}}"#,
+                nodetype, attrs
+            )
+        };
+        let row = |cells: &[&str]| {
+            format!(
+                r#"{{"type": "tableRow", "content": [{}]}}"#,
+                cells.join(", ")
+            )
+        };
+        let header = &cell("tableHeader", "");
+        let plain = &cell("tableCell", r##""background": "#ffffff""##);
+        let coloured = &cell("tableCell", r##""background": "#ffebe6""##);
+
+        let cases = [
+            (
+                table(&[row(&[header, header]), row(&[header, plain])].join(", ")),
+                "table header columns",
+            ),
+            (
+                table(&[row(&[header]), row(&[coloured])].join(", ")),
+                "cell colours",
+            ),
+            (
+                table(&[row(&[plain]), row(&[plain])].join(", ")),
+                "tables without a header row",
+            ),
+        ];
+        for (doc, loss) in &cases {
+            assert_eq!(markdown_losses(doc), vec![*loss]);
+            assert!(!round_trips(doc), "{} survived", loss);
+        }
+
+        let doc = table(&[row(&[header, header]), row(&[plain, plain])].join(", "));
+        assert!(markdown_losses(&doc).is_empty());
+        assert!(round_trips(&doc));
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let markdown = "# Steps\n\n1. Open the **app**\n2. Tap [login](https://example.com)\n   - twice\n\n> Crashes with `nil`\n\n| a | b |\n| --- | --- |\n| 1 | 2 |";
+        let doc = markdown_to_adf(markdown).unwrap();
+        assert_eq!(adf_to_markdown(&doc), markdown);
+    }
+
+    #[test]
+    fn test_jira_adf_to_markdown() {
+        let doc: Node = serde_json::from_str(
+            r#"{"version": 1, "type": "doc", "content": [
+                {"type": "orderedList", "attrs": {"order": 1}, "content": [
+                    {"type": "listItem", "content": [{"type": "paragraph", "content": [
+                        {"type": "mention", "attrs": {"id": "abc", "text": "@Jane", "accessLevel": ""}},
+                        {"type": "text", "text": " to fix "},
+                        {"type": "emoji", "attrs": {"shortName": ":fire:", "id": "1f525", "text": "🔥"}}
+                    ]}]}
+                ]},
+                {"type": "codeBlock", "content": [{"type": "text", "text": "a_b"}]},
+                {"type": "paragraph", "content": [
+                    {"type": "status", "attrs": {"text": "BLOCKED", "color": "red"}},
+                    {"type": "text", "text": " see "},
+                    {"type": "inlineCard", "attrs": {"url": "https://example.com/x"}},
+                    {"type": "text", "text": " (snake_case)"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            adf_to_markdown(&doc),
+            "1. @Jane to fix 🔥\n\n```\na_b\n```\n\nBLOCKED see <https://example.com/x> (snake\\_case)"
+        );
+    }
 }
@@ -0,0 +1,218 @@
+//! Issues written in `$EDITOR` by `jira create`, kept on disk until Jira accepts them.
+//!
+//! Drafts live in `$JIRA_DRAFTS_DIR`, or `<data dir>/jira/drafts`, one Markdown file per
+//! draft, named after its id.
+
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::format::EditorIssue;
+use super::{Error, Result};
+
+/// A saved draft.
+#[derive(Serialize, Debug, Clone)]
+pub struct Draft {
+    pub id: String,
+    /// The title from the draft, or empty if it doesn't have one yet.
+    pub title: String,
+    /// When the draft was last saved, in seconds since the Unix epoch.
+    pub saved: u64,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct Drafts {
+    dir: PathBuf,
+}
+
+impl Drafts {
+    /// The drafts directory for this user. It's only created once a draft is saved.
+    pub fn open() -> Drafts {
+        let dir = match env::var_os("JIRA_DRAFTS_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::data_dir()
+                .map(|dir| dir.join("jira").join("drafts"))
+                .unwrap_or_else(|| env::temp_dir().join("jira-drafts")),
+        };
+        Drafts::new(dir)
+    }
+
+    pub fn new(dir: PathBuf) -> Drafts {
+        Drafts { dir }
+    }
+
+    // Ids come from the command line, so anything but a number (like `../x`) is refused
+    // rather than joined onto the directory.
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidDraftId { id: id.to_owned() });
+        }
+        Ok(self.dir.join(format!("{}.md", id)))
+    }
+
+    /// Save `contents` as draft `id`, or as a new draft if `id` is `None`. Returns the id.
+    pub fn save(&self, id: Option<&str>, contents: &str) -> Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        let id = match id {
+            Some(id) => id.to_owned(),
+            None => {
+                let last = self
+                    .list()?
+                    .iter()
+                    .filter_map(|draft| draft.id.parse::<u32>().ok())
+                    .max();
+                (last.unwrap_or(0) + 1).to_string()
+            }
+        };
+        fs::write(self.path(&id)?, contents)?;
+        Ok(id)
+    }
+
+    /// All drafts, most recently saved first.
+    pub fn list(&self) -> Result<Vec<Draft>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut drafts = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("md") {
+                continue;
+            }
+            // Skip other Markdown files that happen to be there
+            let id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(id) if self.path(id).is_ok() => id.to_owned(),
+                _ => continue,
+            };
+            drafts.push(self.load(id, path)?);
+        }
+
+        drafts.sort_by(|a, b| b.saved.cmp(&a.saved).then_with(|| b.id.cmp(&a.id)));
+        Ok(drafts)
+    }
+
+    fn load(&self, id: String, path: PathBuf) -> Result<Draft> {
+        let contents = fs::read_to_string(&path)?;
+        let saved = fs::metadata(&path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let title = EditorIssue::parse(&contents)
+            .map(|issue| issue.title)
+            .unwrap_or_default();
+        Ok(Draft {
+            id,
+            title,
+            saved,
+            path,
+        })
+    }
+
+    /// The draft with this id, if there is one.
+    pub fn get(&self, id: &str) -> Result<Option<Draft>> {
+        let path = self.path(id)?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+        self.load(id.to_owned(), path).map(Some)
+    }
+
+    /// The most recently saved draft.
+    pub fn latest(&self) -> Result<Option<Draft>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    pub fn read(&self, draft: &Draft) -> Result<String> {
+        Ok(fs::read_to_string(&draft.path)?)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        match fs::remove_file(self.path(id)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How long ago `secs` (since the Unix epoch) was, e.g. "5 minutes ago".
+pub fn ago(secs: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let elapsed = now.saturating_sub(secs);
+    let (count, unit) = match elapsed {
+        0..=59 => return "just now".to_owned(),
+        60..=3599 => (elapsed / 60, "minute"),
+        3600..=86_399 => (elapsed / 3600, "hour"),
+        _ => (elapsed / 86_400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_list_and_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let drafts = Drafts::new(dir.path().join("drafts"));
+        assert!(drafts.list().unwrap().is_empty());
+
+        let first = drafts.save(None, "---\ntype: Bug\n---\nCrash\n").unwrap();
+        let second = drafts.save(None, "Another one\n\nDetails").unwrap();
+        assert_eq!((first.as_str(), second.as_str()), ("1", "2"));
+
+        // Saving over an existing draft keeps its id
+        drafts.save(Some(&first), "Crash on launch\n").unwrap();
+        let draft = drafts.get(&first).unwrap().unwrap();
+        assert_eq!(draft.title, "Crash on launch");
+        assert_eq!(drafts.list().unwrap().len(), 2);
+
+        drafts.delete(&second).unwrap();
+        let ids = drafts
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1"]);
+        assert!(drafts.get(&second).unwrap().is_none());
+        assert_eq!(drafts.save(None, "Third").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_ids_must_be_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("notes.md");
+        fs::write(&outside, "Keep me").unwrap();
+        let drafts = Drafts::new(dir.path().join("drafts"));
+        drafts.save(None, "A draft").unwrap();
+        fs::write(dir.path().join("drafts/README.md"), "Not a draft").unwrap();
+
+        for id in &["../notes", "1/../../notes", "", "-1"] {
+            match drafts.delete(id) {
+                Err(Error::InvalidDraftId { .. }) => {}
+                result => panic!("Unexpected result for {:?}: {:?}", id, result),
+            }
+            assert!(drafts.get(id).is_err());
+            assert!(drafts.save(Some(id), "x").is_err());
+        }
+        assert!(outside.is_file());
+        assert_eq!(drafts.list().unwrap().len(), 1);
+    }
+}
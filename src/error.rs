@@ -0,0 +1,322 @@
+//! The error type returned by every fallible function in this crate.
+
+use reqwest::{header, Response, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The resource doesn't exist, or isn't visible to this user (404).
+    NotFound {
+        message: String,
+    },
+    /// The email/token pair was rejected; the token has probably expired (401).
+    Unauthorized {
+        message: String,
+    },
+    /// The user is authenticated but lacks permission for this operation (403).
+    Forbidden {
+        message: String,
+    },
+    /// Jira is throttling requests (429). `retry_after` is taken from the `Retry-After` header.
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    /// Jira rejected the request (400), along with the reasons it gave.
+    Validation {
+        messages: Vec<String>,
+        errors: HashMap<String, String>,
+    },
+    /// Any other unexpected response from Jira.
+    Api {
+        status: StatusCode,
+        body: String,
+    },
+    /// The request couldn't be sent, or the response couldn't be read.
+    Transport(reqwest::Error),
+    /// The response didn't have the shape we expected.
+    Deserialization(Box<dyn std::error::Error + Send + Sync>),
+    /// A result couldn't be written out as JSON/YAML/CSV.
+    Serialization(Box<dyn std::error::Error + Send + Sync>),
+    /// The Markdown uses a feature that can't be represented in ADF.
+    UnsupportedMarkdown {
+        feature: String,
+    },
+    /// The requested transition doesn't exist for this issue.
+    UnknownTransition {
+        requested: String,
+        available: Vec<String>,
+    },
+    /// The requested link type doesn't exist on this Jira instance.
+    UnknownLinkType {
+        requested: String,
+        available: Vec<String>,
+    },
+    /// There's no field with this name or ID on this Jira instance.
+    UnknownField {
+        requested: String,
+        suggestions: Vec<String>,
+    },
+    /// A field value couldn't be converted to the field's type.
+    InvalidFieldValue {
+        field: String,
+        value: String,
+        expected: String,
+    },
+    /// A draft id that `jira::drafts` couldn't have saved (they're whole numbers).
+    InvalidDraftId {
+        id: String,
+    },
+    /// The front matter of an issue edited in `$EDITOR` couldn't be parsed.
+    FrontMatter {
+        message: String,
+    },
+    /// The config file couldn't be parsed.
+    Config {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// `$EDITOR` couldn't be launched.
+    Editor {
+        editor: String,
+        source: std::io::Error,
+    },
+    Io(std::io::Error),
+}
+
+/// The error body Jira returns for most 4xx responses.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ErrorCollection {
+    #[serde(default)]
+    error_messages: Vec<String>,
+    #[serde(default)]
+    errors: HashMap<String, String>,
+}
+
+impl ErrorCollection {
+    fn message(&self) -> String {
+        let mut messages = self.error_messages.clone();
+        let mut errors = self
+            .errors
+            .iter()
+            .map(|(field, error)| format!("{}: {}", field, error))
+            .collect::<Vec<_>>();
+        errors.sort();
+        messages.append(&mut errors);
+        messages.join("; ")
+    }
+}
+
+impl Error {
+    /// Build an error out of an unsuccessful response. This consumes the response body.
+    pub async fn from_response(response: Response) -> Error {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(e) => return Error::Transport(e),
+        };
+
+        Error::from_parts(status, retry_after, body)
+    }
+
+    fn from_parts(status: StatusCode, retry_after: Option<Duration>, body: String) -> Error {
+        let collection = serde_json::from_str::<ErrorCollection>(&body).ok();
+        let message = match &collection {
+            Some(c) => c.message(),
+            None => body.trim().to_owned(),
+        };
+
+        match status {
+            StatusCode::NOT_FOUND => Error::NotFound { message },
+            StatusCode::UNAUTHORIZED => Error::Unauthorized { message },
+            StatusCode::FORBIDDEN => Error::Forbidden { message },
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited { retry_after },
+            StatusCode::BAD_REQUEST if collection.is_some() => {
+                let collection = collection.unwrap_or_default();
+                Error::Validation {
+                    messages: collection.error_messages,
+                    errors: collection.errors,
+                }
+            }
+            status => Error::Api { status, body },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound { message } => write!(f, "Not found: {}", message),
+            Error::Unauthorized { message } => write!(f, "Unauthorized: {}", message),
+            Error::Forbidden { message } => write!(f, "Forbidden: {}", message),
+            Error::RateLimited {
+                retry_after: Some(d),
+            } => write!(f, "Rate limited, retry after {} seconds", d.as_secs()),
+            Error::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            Error::Validation { messages, errors } => {
+                let collection = ErrorCollection {
+                    error_messages: messages.clone(),
+                    errors: errors.clone(),
+                };
+                write!(f, "Jira rejected the request: {}", collection.message())
+            }
+            Error::Api { status, body } => write!(f, "Got a {} from Jira, {}", status, body),
+            Error::Transport(e) => write!(f, "Failed to talk to Jira: {}", e),
+            Error::Deserialization(e) => write!(f, "Unexpected response from Jira: {}", e),
+            Error::Serialization(e) => write!(f, "Failed to write output: {}", e),
+            Error::UnsupportedMarkdown { feature } => {
+                write!(f, "Markdown {} aren't supported by Jira", feature)
+            }
+            Error::UnknownTransition {
+                requested,
+                available,
+            } if available.is_empty() => write!(f, "Unknown transition '{}'", requested),
+            Error::UnknownTransition {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Unknown transition '{}', expected one of: {}",
+                requested,
+                available.join(", ")
+            ),
+            Error::UnknownLinkType {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Unknown link type '{}', expected one of: {}",
+                requested,
+                available.join(", ")
+            ),
+            Error::UnknownField {
+                requested,
+                suggestions,
+            } if suggestions.is_empty() => write!(f, "Unknown field '{}'", requested),
+            Error::UnknownField {
+                requested,
+                suggestions,
+            } => write!(
+                f,
+                "Unknown field '{}', did you mean: {}",
+                requested,
+                suggestions.join(", ")
+            ),
+            Error::InvalidFieldValue {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value '{}' for {}, expected {}",
+                value, field, expected
+            ),
+            Error::InvalidDraftId { id } => {
+                write!(f, "Invalid draft id '{}', expected a number", id)
+            }
+            Error::FrontMatter { message } => write!(f, "Invalid front matter: {}", message),
+            Error::Config {
+                path: Some(path),
+                message,
+            } => write!(f, "Invalid config file {}: {}", path.display(), message),
+            Error::Config {
+                path: None,
+                message,
+            } => write!(f, "Invalid config: {}", message),
+            Error::Editor { editor, source } => {
+                write!(f, "Failed to launch editor '{}': {}", editor, source)
+            }
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Deserialization(e) => Some(e.as_ref()),
+            Error::Serialization(e) => Some(e.as_ref()),
+            Error::Editor { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            Error::Deserialization(Box::new(e))
+        } else {
+            Error::Transport(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Deserialization(Box::new(e))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_error() {
+        let body = r#"{"errorMessages":[],"errors":{"summary":"You must specify a summary of the issue."}}"#;
+        match Error::from_parts(StatusCode::BAD_REQUEST, None, body.to_owned()) {
+            Error::Validation { errors, .. } => assert_eq!(
+                errors.get("summary").unwrap(),
+                "You must specify a summary of the issue."
+            ),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_not_found_message() {
+        let body = r#"{"errorMessages":["Issue does not exist or you do not have permission to see it."],"errors":{}}"#;
+        let error = Error::from_parts(StatusCode::NOT_FOUND, None, body.to_owned());
+        assert_eq!(
+            error.to_string(),
+            "Not found: Issue does not exist or you do not have permission to see it."
+        );
+    }
+
+    #[test]
+    fn test_rate_limited() {
+        let error = Error::from_parts(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(Duration::from_secs(30)),
+            String::new(),
+        );
+        match error {
+            Error::RateLimited { retry_after } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(30)))
+            }
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
+}
@@ -1,86 +1,304 @@
-use super::convert;
 use super::graphql::PullRequestStatus;
 use super::model::IssueEpic;
-use super::model::IssueStatus;
+use super::model::{IssueStatus, StatusCategoryKey, Votes, Watches};
+use super::output::{field_text, Column, DEFAULT_COLUMNS};
+use super::sort::{arrange, default_sort, status_order};
+use super::util::local_datetime;
+use chrono::{Local, NaiveDate};
 use colored::Colorize;
 use prettytable::format;
-use prettytable::Table;
 use prettytable::{cell, row};
+use prettytable::{Cell, Row, Table};
 use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::env;
-use std::error::Error;
 use std::fs;
 use std::io::Write;
 use std::process::Command;
-use tempfile::NamedTempFile;
-
-// Remove all lines starting with a '>'
-fn remove_commented_lines<'a>(
-    lines: impl Iterator<Item = &'a str>,
-) -> impl Iterator<Item = &'a str> {
-    lines.filter(|&line| {
-        if let Some(c) = line.chars().next() {
-            c != '>'
-        } else {
-            false
-        }
-    })
-}
+use tempfile::Builder;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+mod terminal;
+
+pub use terminal::{adf_to_terminal, terminal_width};
 
-pub async fn text_from_editor(
-    template: &str,
-) -> Result<Option<(String, convert::Node)>, Box<dyn Error>> {
+/// Open `contents` in `$EDITOR` (falling back to nano), and return what was saved.
+pub fn edit_in_editor(contents: &str) -> super::Result<String> {
     let editor = env::var("EDITOR").unwrap_or("nano".to_owned());
 
-    let temp_file = NamedTempFile::new()?;
-    temp_file
-        .as_file()
-        .write_all(template.as_bytes())
-        .expect("Failed to write template to the temp file.");
+    // The extension lets editors pick Markdown highlighting
+    let temp_file = Builder::new().suffix(".md").tempfile()?;
+    temp_file.as_file().write_all(contents.as_bytes())?;
 
     let temp_path = temp_file.into_temp_path();
 
-    let canonical_path = fs::canonicalize(&temp_path).unwrap();
-    Command::new(editor)
+    let canonical_path = fs::canonicalize(&temp_path)?;
+    Command::new(&editor)
         .arg(&canonical_path)
         .status()
-        .expect("Failed to open $EDITOR");
-
-    let contents =
-        fs::read_to_string(&temp_path).expect(&format!("Failed to read file: {:?}", temp_path));
-    let contents = contents.trim().split("\n");
-    let mut contents = remove_commented_lines(contents);
-    let (first_line, other_lines) = (contents.next(), contents.collect::<Vec<_>>().join("\n"));
+        .map_err(|source| super::Error::Editor { editor, source })?;
 
+    let contents = fs::read_to_string(&temp_path)?;
     temp_path.close()?;
 
-    Ok(match (first_line, other_lines) {
-        (None, _) => None,
-        (Some(""), _) => None,
-        (Some(first_line), other_lines) => {
-            let title = first_line.trim().to_owned();
-            let description = other_lines.trim().to_owned();
-            Some((title, convert::markdown_to_adf(&description)))
+    Ok(contents)
+}
+
+// Distinguish a field that's present but empty (`Some(None)`) from one that's left out
+// (`None`).
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Issue fields set in the front matter of an editor buffer. Fields that are left out
+/// aren't changed, and blank ones are cleared (`Some(None)`).
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    /// The issue type, e.g. `Bug`. Only used when creating an issue.
+    #[serde(rename = "type")]
+    pub issuetype: Option<String>,
+    pub labels: Option<Vec<String>>,
+    pub components: Option<Vec<String>>,
+    /// The key of the epic to file the issue under. Only used when creating an issue.
+    #[serde(deserialize_with = "present")]
+    pub epic: Option<Option<String>>,
+    /// The key of the parent issue, for subtasks. Only used when creating an issue.
+    #[serde(deserialize_with = "present")]
+    pub parent: Option<Option<String>>,
+    /// A name or email address, or `me`.
+    #[serde(deserialize_with = "present")]
+    pub assignee: Option<Option<String>>,
+    #[serde(deserialize_with = "present")]
+    pub priority: Option<Option<String>>,
+    /// Any other fields, by name or ID (e.g. `Story Points` or `customfield_10016`). Only
+    /// used when creating an issue.
+    pub fields: Option<HashMap<String, serde_json::Value>>,
+}
+
+// TOML has no null, so an empty string also clears a field.
+fn blank_to_none(value: Option<Option<String>>) -> Option<Option<String>> {
+    value.map(|v| v.filter(|v| !v.trim().is_empty()))
+}
+
+impl FrontMatter {
+    /// The fields that are set, but that only apply to new issues.
+    pub fn create_only_fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.issuetype.is_some() {
+            fields.push("type");
+        }
+        if self.epic.is_some() {
+            fields.push("epic");
+        }
+        if self.parent.is_some() {
+            fields.push("parent");
+        }
+        if self.fields.is_some() {
+            fields.push("fields");
+        }
+        fields
+    }
+
+    fn parse(text: &str, format: FrontMatterFormat) -> super::Result<FrontMatter> {
+        // A block of nothing but comments isn't valid YAML on its own
+        let has_fields = text.lines().any(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        });
+        if !has_fields {
+            return Ok(FrontMatter::default());
+        }
+
+        let front_matter: FrontMatter = match format {
+            FrontMatterFormat::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            FrontMatterFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        }
+        .map_err(|message| super::Error::FrontMatter { message })?;
+
+        Ok(FrontMatter {
+            epic: blank_to_none(front_matter.epic),
+            parent: blank_to_none(front_matter.parent),
+            assignee: blank_to_none(front_matter.assignee),
+            priority: blank_to_none(front_matter.priority),
+            ..front_matter
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FrontMatterFormat {
+    Yaml,
+    Toml,
+}
+
+/// An issue as written in the editor: front matter between `---` (YAML) or `+++` (TOML)
+/// lines, then the title on the first line, and the rest as a Markdown description.
+#[derive(Debug, Default, PartialEq)]
+pub struct EditorIssue {
+    pub front_matter: FrontMatter,
+    pub title: String,
+    pub description: String,
+}
+
+impl EditorIssue {
+    pub fn parse(text: &str) -> super::Result<EditorIssue> {
+        let mut lines = text.trim_start().lines();
+        let mut front_matter = Vec::new();
+        let mut body = Vec::new();
+
+        let mut first = lines.next();
+        let (delimiter, format) = match first.map(str::trim_end) {
+            Some("+++") => ("+++", Some(FrontMatterFormat::Toml)),
+            Some("---") => ("---", Some(FrontMatterFormat::Yaml)),
+            _ => ("", None),
+        };
+        if format.is_some() {
+            loop {
+                match lines.next() {
+                    Some(line) if line.trim_end() == delimiter => break,
+                    Some(line) => front_matter.push(line),
+                    None => {
+                        return Err(super::Error::FrontMatter {
+                            message: format!("missing the closing '{}'", delimiter),
+                        })
+                    }
+                }
+            }
+            first = None;
         }
-    })
+        body.extend(first);
+        body.extend(lines);
+
+        let front_matter = front_matter.join("\n");
+        let body = body.join("\n");
+
+        let front_matter = match format {
+            Some(format) => FrontMatter::parse(&front_matter, format)?,
+            None => FrontMatter::default(),
+        };
+
+        let mut lines = body.trim().splitn(2, '\n');
+        Ok(EditorIssue {
+            front_matter,
+            title: lines.next().unwrap_or_default().trim().to_owned(),
+            description: lines.next().unwrap_or_default().trim().to_owned(),
+        })
+    }
+}
+
+// Whether a value has to be quoted to be read back as the same string.
+fn needs_yaml_quotes(value: &str) -> bool {
+    let special_word = matches!(
+        value.to_lowercase().as_str(),
+        "true" | "false" | "yes" | "no" | "on" | "off" | "null" | "~"
+    );
+    let plain = value
+        .chars()
+        .all(|c| c.is_alphanumeric() || " -_./()'@+".contains(c));
+    let starts_plain = value.chars().next().map_or(false, |c| c.is_alphanumeric());
+
+    special_word || !plain || !starts_plain || value.parse::<f64>().is_ok()
+}
+
+/// Write a value for the front matter. An empty value leaves the field blank.
+pub fn yaml_scalar(value: Option<&str>) -> String {
+    match value {
+        Some(value) if needs_yaml_quotes(value) => serde_json::to_string(value).unwrap_or_default(),
+        Some(value) => value.to_owned(),
+        None => String::new(),
+    }
+}
+
+/// Write a list for the front matter, in flow style (`[a, b]`).
+pub fn yaml_list(values: &[String]) -> String {
+    let values = values
+        .iter()
+        .map(|v| {
+            // Commas and brackets are special inside a flow list too
+            if needs_yaml_quotes(v) || v.contains(',') {
+                serde_json::to_string(v).unwrap_or_default()
+            } else {
+                v.to_owned()
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
 }
 
-pub fn issue_type_colored(t: IssueStatus) -> colored::ColoredString {
+pub fn issue_type_colored(t: &IssueStatus) -> colored::ColoredString {
     let s = t.to_string();
 
-    match t {
-        IssueStatus::Closed => s.red(),
-        IssueStatus::Done => s.green(),
-        IssueStatus::InProgress => s.bright_cyan(),
-        IssueStatus::InReview => s.truecolor(145, 115, 188).bold(),
-        IssueStatus::ToDo => s.white(),
-        IssueStatus::ToDoLegacy => s.white(),
-        IssueStatus::Logged => s.dimmed().italic().truecolor(180, 180, 180),
-        IssueStatus::SupportTriaged => s.dimmed().italic().truecolor(180, 180, 180),
+    match t.status_category.key {
+        StatusCategoryKey::New => s.white(),
+        StatusCategoryKey::Indeterminate => s.bright_cyan(),
+        StatusCategoryKey::Done => s.green(),
+        StatusCategoryKey::Undefined => s.dimmed().italic().truecolor(180, 180, 180),
+    }
+}
+
+/// The watcher count, and whether you're one of them.
+fn watchers_text(watches: &Watches) -> String {
+    if watches.is_watching {
+        format!("{}, including you", watches.watch_count)
+    } else {
+        watches.watch_count.to_string()
+    }
+}
+
+/// The vote count, and whether you voted; `None` if nobody has.
+fn votes_text(votes: &Votes) -> Option<String> {
+    match votes.votes {
+        0 => None,
+        count if votes.has_voted => Some(format!("{}, including yours", count)),
+        count => Some(count.to_string()),
     }
 }
 
+/// A due date, in red once it's passed on an issue that isn't done.
+fn due_colored(due: NaiveDate, done: bool) -> colored::ColoredString {
+    let s = due.format("%Y-%m-%d").to_string();
+    if !done && due < Local::now().date_naive() {
+        s.red()
+    } else {
+        s.normal()
+    }
+}
+
+fn is_done(issue: &super::model::Issue) -> bool {
+    issue.resolution.is_some()
+        || issue
+            .status
+            .as_ref()
+            .map_or(false, |s| s.status_category.key == StatusCategoryKey::Done)
+}
+
+/// An empty table with `|` between columns and a `-` rule above and below, as every table but
+/// the issue summary uses.
+fn bordered_table() -> Table {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new()
+        .column_separator('|')
+        .borders('|')
+        .separators(
+            &[format::LinePosition::Top, format::LinePosition::Bottom],
+            format::LineSeparator::new('-', '+', '+', '+'),
+        )
+        .padding(1, 1)
+        .build();
+    table.set_format(format);
+    table
+}
+
 pub fn issue_table(issue: super::model::IssueSearchResult) {
+    let done = is_done(&issue.fields);
+    // Issue lists nested in the table sit to the right of its headings
+    let nested_width = terminal_width().saturating_sub(16);
     let mut table = Table::new();
     let format = format::FormatBuilder::new()
         .column_separator('|')
@@ -110,7 +328,21 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
     if let Some(status) = issue.fields.status {
         table.add_row(row![
             br->"Status".dimmed(),
-            format!("{}", status)
+            issue_type_colored(&status)
+        ]);
+    }
+
+    if let Some(resolution) = issue.fields.resolution {
+        table.add_row(row![
+            br->"Resolution".dimmed(),
+            resolution.name
+        ]);
+    }
+
+    if let Some(priority) = issue.fields.priority {
+        table.add_row(row![
+            br->"Priority".dimmed(),
+            priority.name
         ]);
     }
 
@@ -121,6 +353,13 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
         ]);
     }
 
+    if let Some(reporter) = issue.fields.reporter {
+        table.add_row(row![
+            br->"Reporter".dimmed(),
+            reporter.display_name
+        ]);
+    }
+
     if let Some(epic) = issue.fields.epic {
         let epic = match epic {
             IssueEpic::Key(k) => k,
@@ -156,6 +395,70 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
         issue.fields.issuetype.name
     ]);
 
+    if let Some(versions) = issue.fields.fix_versions {
+        if !versions.is_empty() {
+            let versions = versions
+                .iter()
+                .map(|v| v.name.to_owned())
+                .collect::<Vec<_>>();
+            table.add_row(row![
+                br->"Fix versions".dimmed(),
+                versions.join(", ")
+            ]);
+        }
+    }
+
+    if let Some(created) = issue.fields.created {
+        table.add_row(row![
+            br->"Created".dimmed(),
+            local_datetime(&created)
+        ]);
+    }
+
+    if let Some(updated) = issue.fields.updated {
+        table.add_row(row![
+            br->"Updated".dimmed(),
+            local_datetime(&updated)
+        ]);
+    }
+
+    if let Some(due) = issue.fields.duedate {
+        table.add_row(row![
+            br->"Due".dimmed(),
+            due_colored(due, done)
+        ]);
+    }
+
+    if let Some(timetracking) = issue.fields.timetracking {
+        let rows = [
+            ("Estimate", timetracking.original_estimate),
+            ("Remaining", timetracking.remaining_estimate),
+            ("Logged", timetracking.time_spent),
+        ];
+        for (name, value) in rows.iter() {
+            if let Some(value) = value {
+                table.add_row(row![
+                    br->name.dimmed(),
+                    value
+                ]);
+            }
+        }
+    }
+
+    if let Some(watches) = &issue.fields.watches {
+        table.add_row(row![
+            br->"Watchers".dimmed(),
+            watchers_text(watches)
+        ]);
+    }
+
+    if let Some(voted) = issue.fields.votes.as_ref().and_then(votes_text) {
+        table.add_row(row![
+            br->"Votes".dimmed(),
+            voted
+        ]);
+    }
+
     if let Some(parent) = issue.fields.parent {
         table.add_row(row![
             br->"Parent".dimmed(),
@@ -165,7 +468,13 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
 
     if let Some(subtasks) = issue.subtasks {
         if subtasks.len() > 0 {
-            let sub_table = issues_table(subtasks, &IssuesTableConfig::sorted());
+            let sub_table = issues_table(
+                arrange(subtasks, &default_sort()),
+                &IssuesTableConfig {
+                    width: Some(nested_width),
+                    ..Default::default()
+                },
+            );
             table.add_row(row![
                 br->"Subtasks".dimmed(),
                 sub_table
@@ -175,11 +484,13 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
 
     if let Some(epic_issues) = issue.epic_issues {
         let sub_table = issues_table(
-            epic_issues,
+            arrange(epic_issues, &default_sort()),
             &IssuesTableConfig {
-                sort: true,
-                skip_type: true,
-                ..Default::default()
+                columns: ["type", "status", "key", "summary", "assignee"]
+                    .iter()
+                    .map(|c| Column::Builtin(c))
+                    .collect(),
+                width: Some(nested_width),
             },
         );
         table.add_row(row![
@@ -188,18 +499,41 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
         ]);
     };
 
+    for field in issue.field_values.iter().flatten() {
+        let value = match field_text(&field.value) {
+            text if text.is_empty() => "<none>".dimmed(),
+            text => text.normal(),
+        };
+        table.add_row(row![
+            br->field.name.dimmed(),
+            value
+        ]);
+    }
+
+    if let Some(links) = issue.fields.issuelinks {
+        if !links.is_empty() {
+            table.add_row(row![
+                br->"Links".dimmed(),
+                links_table(&links)
+            ]);
+        }
+    }
+
+    if let Some(attachments) = issue.fields.attachment {
+        if !attachments.is_empty() {
+            let attachments = attachments
+                .iter()
+                .map(|a| format!("{} ({})", a.filename, human_size(a.size)))
+                .collect::<Vec<_>>();
+            table.add_row(row![
+                br->"Attachments".dimmed(),
+                attachments.join("\n")
+            ]);
+        }
+    }
+
     if let Some(prs) = issue.pull_requests {
-        let mut pr_table = Table::new();
-        let format = format::FormatBuilder::new()
-            .column_separator('|')
-            .padding(1, 1)
-            .borders('|')
-            .separators(
-                &[format::LinePosition::Top, format::LinePosition::Bottom],
-                format::LineSeparator::new('-', '+', '+', '+'),
-            )
-            .build();
-        pr_table.set_format(format);
+        let mut pr_table = bordered_table();
 
         for pr in &prs {
             let name = Regex::new(r"(\[[A-Z]+-\d+\]\s)?(.*)")
@@ -208,15 +542,18 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
                 .unwrap()
                 .get(2)
                 .unwrap();
-            let url = Regex::new(r"github.com/[^/]*/[^/]*/pull/(\d+)")
+            // Non-GitHub PRs don't have a number we can extract, so show the whole URL
+            let url = match Regex::new(r"github.com/[^/]*/[^/]*/pull/(\d+)")
                 .unwrap()
                 .captures(&pr.url)
-                .unwrap()
-                .get(1)
-                .unwrap();
+                .and_then(|c| c.get(1))
+            {
+                Some(number) => format!("#{}", number.as_str()),
+                None => pr.url.to_owned(),
+            };
 
             pr_table.add_row(row![
-                format!("#{}", url.as_str()).bold(),
+                url.bold(),
                 name.as_str().italic(),
                 l->pr_status_colored(&pr.status)
             ]);
@@ -229,83 +566,392 @@ pub fn issue_table(issue: super::model::IssueSearchResult) {
     };
 
     table.printstd();
+
+    // Printed below the table, where it can use the full width of the terminal
+    if let Some(description) = issue.fields.description {
+        let description = adf_to_terminal(&description, terminal_width());
+        if !description.trim().is_empty() {
+            println!("\n{}\n{}", "Description".dimmed(), description);
+        }
+    }
+
+    if let Some(environment) = issue.fields.environment {
+        let environment = adf_to_terminal(&environment, terminal_width());
+        if !environment.trim().is_empty() {
+            println!("\n{}\n{}", "Environment".dimmed(), environment);
+        }
+    }
+
+    if let Some(page) = issue.comments {
+        if !page.comments.is_empty() {
+            let heading = if page.total > page.comments.len() {
+                format!(
+                    "Comments ({} of {}, see them all with `jira comment {} --list`)",
+                    page.comments.len(),
+                    page.total,
+                    issue.key
+                )
+            } else {
+                "Comments".to_owned()
+            };
+            println!(
+                "\n{}\n{}",
+                heading.dimmed(),
+                comments_text(&page.comments, terminal_width())
+            );
+        }
+    }
 }
 
-#[derive(Debug, Default)]
-pub struct IssuesTableConfig {
-    pub sort: bool,
-    pub skip_type: bool,
+/// Trim a Jira timestamp (`2020-06-01T09:30:00.000+0000`) down to the minute.
+fn short_date(date: &str) -> String {
+    date.chars()
+        .take(16)
+        .map(|c| if c == 'T' { ' ' } else { c })
+        .collect()
 }
 
-impl IssuesTableConfig {
-    pub fn sorted() -> IssuesTableConfig {
-        IssuesTableConfig { sort: true, ..Default::default() }
+/// Render comments for the terminal: who wrote each one and when, then its body.
+pub fn comments_text(comments: &[super::model::Comment], width: usize) -> String {
+    let mut blocks = Vec::new();
+    for comment in comments {
+        let author = comment
+            .author
+            .as_ref()
+            .map(|author| author.display_name.as_str())
+            .unwrap_or("Anonymous");
+        let edited = if comment.updated.is_empty() || comment.updated == comment.created {
+            ""
+        } else {
+            ", edited"
+        };
+        let heading = format!(
+            "{} {}",
+            author.bold(),
+            format!("{}{} #{}", short_date(&comment.created), edited, comment.id).dimmed()
+        );
+
+        let body = match &comment.body {
+            Some(body) => adf_to_terminal(body, width.saturating_sub(2)),
+            None => String::new(),
+        };
+        let body = body
+            .trim_end()
+            .lines()
+            .map(|line| format!("  {}", line).trim_end().to_owned())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if body.is_empty() {
+            blocks.push(heading);
+        } else {
+            blocks.push(format!("{}\n{}", heading, body));
+        }
     }
+    blocks.join("\n\n")
 }
 
-pub fn issues_table(
-    mut issues: Vec<super::model::IssueSearchResult>,
-    config: &IssuesTableConfig,
-) -> Table {
-    let mut table = Table::new();
-    let format = format::FormatBuilder::new()
-        .column_separator('|')
-        .borders('|')
-        .separators(
-            &[format::LinePosition::Top, format::LinePosition::Bottom],
-            format::LineSeparator::new('-', '+', '+', '+'),
+/// An issue's links, grouped by how they relate to it ("blocks", "is blocked by", ...).
+pub fn links_table(links: &[super::model::IssueLink]) -> Table {
+    let mut table = bordered_table();
+
+    let mut links = links.iter().filter_map(|l| l.other()).collect::<Vec<_>>();
+    links.sort_by_key(|(relation, issue)| {
+        let status = issue.fields.as_ref().and_then(|f| f.status.as_ref());
+        (
+            relation.to_owned(),
+            status_order(status),
+            issue.key.to_owned(),
         )
-        .padding(1, 1)
-        .build();
-    table.set_format(format);
+    });
 
-    if config.sort {
-        issues.sort_by(|x, y| {
-            let x_type = &x.fields.issuetype.name;
-            let x_parent = match &x.fields.parent {
-                Some(parent) => {
-                    format!("{:?}{}", parent.fields.as_ref().unwrap().status, parent.key)
-                }
-                None => String::new(),
-            };
+    let mut previous = None;
+    for (relation, issue) in links {
+        // Only name each group once
+        let group = if previous == Some(relation) {
+            String::new()
+        } else {
+            relation.to_owned()
+        };
+        previous = Some(relation);
 
-            let y_type = &y.fields.issuetype.name;
-            let y_parent = match &y.fields.parent {
-                Some(parent) => {
-                    format!("{:?}{}", parent.fields.as_ref().unwrap().status, parent.key)
-                }
-                None => String::new(),
-            };
+        let fields = issue.fields.as_ref();
+        let status = fields
+            .and_then(|f| f.status.as_ref())
+            .map(issue_type_colored)
+            .unwrap_or_else(|| "".normal());
+        let summary = fields.map(|f| f.summary.as_str()).unwrap_or_default();
+        table.add_row(row![
+            group.dimmed(),
+            br->status,
+            bc->issue.key,
+            summary
+        ]);
+    }
 
-            format!("{}{}{:?}{}", x_type, x_parent, x.fields.status, x.key).cmp(&format!(
-                "{}{}{:?}{}",
-                y_type, y_parent, y.fields.status, y.key
-            ))
-        });
+    table
+}
+
+/// A file size, e.g. "12.3 KB".
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
     }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
 
-    for issue in issues {
-        let status = issue.fields.status.unwrap_or_default();
-        let status = issue_type_colored(status);
+pub fn attachments_table(attachments: &[super::model::Attachment]) -> Table {
+    let mut table = bordered_table();
 
-        let summary = match issue.fields.parent {
-            Some(_) => format!("| {}", issue.fields.summary).truecolor(180, 180, 180),
-            None => issue.fields.summary.white(),
+    for attachment in attachments {
+        let author = match &attachment.author {
+            Some(author) => author.display_name.white(),
+            None => "<none>".dimmed(),
         };
+        table.add_row(row![
+            bc->attachment.id,
+            attachment.filename,
+            r->human_size(attachment.size),
+            author,
+            short_date(&attachment.created).dimmed()
+        ]);
+    }
 
-        let assignee = if let Some(assignee) = issue.fields.assignee {
-            assignee.display_name.to_owned().white()
-        } else {
-            "<none>".dimmed()
+    table
+}
+
+pub fn worklogs_table(worklogs: &[super::model::Worklog]) -> Table {
+    let mut table = bordered_table();
+
+    for worklog in worklogs {
+        let author = match &worklog.author {
+            Some(author) => author.display_name.white(),
+            None => "<none>".dimmed(),
+        };
+        // Only the gist of the comment fits in a table
+        let comment = worklog
+            .comment
+            .as_ref()
+            .map(|comment| {
+                super::convert::adf_to_markdown(comment)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        table.add_row(row![
+            short_date(&worklog.started).dimmed(),
+            author,
+            r->worklog.time_spent,
+            comment
+        ]);
+    }
+
+    let total = worklogs.iter().map(|w| w.time_spent_seconds).sum();
+    table.add_row(row![
+        "",
+        b->"Total",
+        br->super::util::format_duration(total),
+        ""
+    ]);
+
+    table
+}
+
+pub fn users_table(users: &[super::model::User]) -> Table {
+    let mut table = bordered_table();
+
+    for user in users {
+        let email = match &user.email_address {
+            Some(email) => email.normal(),
+            None => "<hidden>".dimmed(),
+        };
+        table.add_row(row![b->user.display_name, email]);
+    }
+
+    table
+}
+
+pub fn fields_table(fields: &[super::model::Field]) -> Table {
+    let mut table = bordered_table();
+
+    for field in fields {
+        let schema = match &field.schema {
+            Some(schema) => match &schema.items {
+                Some(items) => format!("{} of {}", schema.field_type, items),
+                None => schema.field_type.to_owned(),
+            },
+            None => String::new(),
         };
+        table.add_row(row![b->field.name, field.id.dimmed(), schema]);
+    }
 
-        let row = if config.skip_type {
-            row![ c->issue.fields.issuetype.name, br->status, bc->issue.key, summary, assignee ]
+    table
+}
+
+pub fn drafts_table(drafts: &[super::drafts::Draft]) -> Table {
+    let mut table = bordered_table();
+
+    for draft in drafts {
+        let title = if draft.title.is_empty() {
+            "<untitled>".dimmed()
         } else {
-            row![ br->status, bc->issue.key, summary, assignee ]
+            draft.title.white()
         };
+        table.add_row(row![
+            bc->draft.id,
+            super::drafts::ago(draft.saved).dimmed(),
+            title
+        ]);
+    }
+
+    table
+}
+
+/// Summaries are cut down to fit the terminal, but never below this.
+const MIN_SUMMARY_WIDTH: usize = 20;
+
+#[derive(Debug, Default, Clone)]
+pub struct IssuesTableConfig {
+    /// The columns to show, under a heading row. Empty shows the default columns (see
+    /// `output::DEFAULT_COLUMNS`), without headings.
+    pub columns: Vec<Column>,
+    /// Cut summaries short so the table fits in this many terminal columns.
+    pub width: Option<usize>,
+}
+
+/// Shorten `text` to at most `width` terminal columns, ending it with an ellipsis.
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_owned();
+    }
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width + 1 > width {
+            break;
+        }
+        truncated.push(c);
+        used += char_width;
+    }
+    truncated.push('…');
+    truncated
+}
+
+// The plain text of a cell, before it's truncated and coloured.
+fn cell_text(issue: &super::model::IssueSearchResult, column: &Column) -> String {
+    match column {
+        Column::Builtin("summary") if issue.fields.parent.is_some() => {
+            format!("| {}", issue.fields.summary)
+        }
+        Column::Builtin("assignee") if issue.fields.assignee.is_none() => "<none>".to_owned(),
+        column => column.value(issue),
+    }
+}
 
-        table.add_row(row);
+fn issue_cell(issue: &super::model::IssueSearchResult, column: &Column, text: String) -> Cell {
+    match column {
+        Column::Builtin("status") => {
+            let status = issue.fields.status.clone().unwrap_or_default();
+            Cell::new(&issue_type_colored(&status).to_string()).style_spec("br")
+        }
+        Column::Builtin("key") => Cell::new(&text).style_spec("bc"),
+        Column::Builtin("type") => Cell::new(&text).style_spec("c"),
+        Column::Builtin("summary") if issue.fields.parent.is_some() => {
+            Cell::new(&text.truecolor(180, 180, 180).to_string())
+        }
+        Column::Builtin("summary") => Cell::new(&text.white().to_string()),
+        Column::Builtin("assignee") if issue.fields.assignee.is_none() => {
+            Cell::new(&text.dimmed().to_string())
+        }
+        Column::Builtin("assignee") => Cell::new(&text.white().to_string()),
+        Column::Builtin("due") => match issue.fields.duedate {
+            Some(due) => Cell::new(&due_colored(due, is_done(&issue.fields)).to_string()),
+            None => Cell::new(&text),
+        },
+        _ => Cell::new(&text),
+    }
+}
+
+pub fn issues_table(
+    issues: Vec<super::model::IssueSearchResult>,
+    config: &IssuesTableConfig,
+) -> Table {
+    let mut table = bordered_table();
+    table.get_format().separator(
+        format::LinePosition::Title,
+        format::LineSeparator::new('-', '+', '+', '+'),
+    );
+
+    let headings = !config.columns.is_empty();
+    let columns = if headings {
+        config.columns.clone()
+    } else {
+        DEFAULT_COLUMNS
+            .iter()
+            .map(|c| Column::Builtin(c))
+            .collect::<Vec<_>>()
+    };
+
+    let mut rows = issues
+        .iter()
+        .map(|issue| {
+            columns
+                .iter()
+                .map(|column| cell_text(issue, column))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Give the summary whatever room the other columns leave
+    let summary = columns
+        .iter()
+        .position(|c| *c == Column::Builtin("summary"));
+    if let (Some(width), Some(summary)) = (config.width, summary) {
+        let mut used = 0;
+        for (i, column) in columns.iter().enumerate() {
+            if i == summary {
+                continue;
+            }
+            let heading = if headings { column.title().width() } else { 0 };
+            used += rows
+                .iter()
+                .map(|row| row[i].width())
+                .max()
+                .unwrap_or(0)
+                .max(heading);
+        }
+        // A space either side of each column, and a separator (or border) between them
+        used += columns.len() * 3 + 1;
+        let available = width.saturating_sub(used).max(MIN_SUMMARY_WIDTH);
+        for row in &mut rows {
+            row[summary] = truncate(&row[summary], available);
+        }
+    }
+
+    if headings {
+        table.set_titles(Row::new(
+            columns
+                .iter()
+                .map(|c| Cell::new(&c.title().dimmed().to_string()))
+                .collect(),
+        ));
+    }
+
+    for (issue, row) in issues.iter().zip(rows) {
+        let cells = columns
+            .iter()
+            .zip(row)
+            .map(|(column, text)| issue_cell(issue, column, text))
+            .collect();
+        table.add_row(Row::new(cells));
     }
 
     table
@@ -320,3 +966,119 @@ fn pr_status_colored(pr: &PullRequestStatus) -> colored::ColoredString {
         PullRequestStatus::Merged => s.truecolor(186, 150, 255),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Crash on launch", 20), "Crash on launch");
+        assert_eq!(truncate("Crash on launch", 9), "Crash on…");
+        // Wide characters take two columns each
+        assert_eq!(truncate("日本語のテキスト", 7), "日本語…");
+    }
+
+    #[test]
+    fn test_watchers_and_votes_text() {
+        let watches = |count, is_watching| Watches {
+            watch_count: count,
+            is_watching,
+            watchers: None,
+        };
+        assert_eq!(watchers_text(&watches(3, true)), "3, including you");
+        assert_eq!(watchers_text(&watches(2, false)), "2");
+        assert_eq!(watchers_text(&watches(0, false)), "0");
+
+        let votes = |votes, has_voted| Votes { votes, has_voted };
+        assert_eq!(votes_text(&votes(0, false)), None);
+        assert_eq!(votes_text(&votes(4, true)).unwrap(), "4, including yours");
+        assert_eq!(votes_text(&votes(1, false)).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(12_595), "12.3 KB");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0 MB");
+    }
+
+    #[test]
+    fn test_comments_text() {
+        colored::control::set_override(false);
+        let comments: Vec<super::super::model::Comment> = serde_json::from_str(
+            r#"[{"id": "10001", "author": {"accountId": "u1", "displayName": "Jane Doe"},
+                 "body": {"type": "doc", "version": 1, "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Fixed in 2.1"}]}]},
+                 "created": "2020-06-01T09:30:00.000+0000", "updated": "2020-06-02T10:00:00.000+0000"},
+                {"id": "10002", "created": "2020-06-03T08:00:00.000+0000"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            comments_text(&comments, 80),
+            "Jane Doe 2020-06-01 09:30, edited #10001\n  Fixed in 2.1\n\nAnonymous 2020-06-03 08:00 #10002"
+        );
+    }
+
+    #[test]
+    fn test_parse_editor_issue() {
+        let issue = EditorIssue::parse(
+            "---\n# A comment\nlabels: [ios, \"a, b\"]\nassignee:\n---\nCrash on launch\n\n> Steps\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            issue,
+            EditorIssue {
+                front_matter: FrontMatter {
+                    labels: Some(vec!["ios".to_owned(), "a, b".to_owned()]),
+                    assignee: Some(None),
+                    ..Default::default()
+                },
+                title: "Crash on launch".to_owned(),
+                description: "> Steps".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_editor_issue_without_front_matter() {
+        let issue = EditorIssue::parse("Title\nBody").unwrap();
+        assert_eq!(issue.front_matter, FrontMatter::default());
+        assert_eq!(issue.title, "Title");
+    }
+
+    #[test]
+    fn test_parse_toml_front_matter() {
+        let issue = EditorIssue::parse(
+            "+++\ntype = \"Bug\"\nassignee = \"\"\n[fields]\ncustomfield_10016 = 3\n+++\nTitle",
+        )
+        .unwrap();
+
+        let front_matter = issue.front_matter;
+        assert_eq!(front_matter.issuetype.as_deref(), Some("Bug"));
+        assert_eq!(front_matter.assignee, Some(None));
+        assert_eq!(
+            front_matter.fields.unwrap()["customfield_10016"],
+            serde_json::json!(3)
+        );
+        assert_eq!(issue.title, "Title");
+    }
+
+    #[test]
+    fn test_unknown_front_matter_field() {
+        assert!(EditorIssue::parse("---\nlabel: [ios]\n---\nTitle").is_err());
+    }
+
+    #[test]
+    fn test_yaml_values_round_trip() {
+        let labels = vec!["ios".to_owned(), "a, b".to_owned(), "123".to_owned()];
+        let yaml = format!(
+            "labels: {}\npriority: {}",
+            yaml_list(&labels),
+            yaml_scalar(Some("yes"))
+        );
+        let front_matter: FrontMatter = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(front_matter.labels, Some(labels));
+        assert_eq!(front_matter.priority, Some(Some("yes".to_owned())));
+    }
+}
@@ -0,0 +1,727 @@
+//! Render ADF documents for the terminal: inline styles via `colored`, text wrapped to
+//! the terminal width, box-drawn tables, highlighted code and clickable (OSC 8) links.
+
+use colored::Colorize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::convert::{self, BlockNodeType, InlineNodeType, Mark, Node};
+
+/// The width to wrap to: the terminal's, then `$COLUMNS`, then 80.
+pub fn terminal_width() -> usize {
+    if let Some((terminal_size::Width(width), _)) = terminal_size::terminal_size() {
+        return width as usize;
+    }
+
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Render an ADF document (or any node in one), wrapped to `width` columns.
+pub fn adf_to_terminal(node: &Node, width: usize) -> String {
+    render_block(node, width, 0)
+        .into_iter()
+        .map(|line| line.text)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A rendered line, and the number of columns it takes up once escape codes are hidden.
+#[derive(Debug, Default)]
+struct Line {
+    text: String,
+    width: usize,
+}
+
+impl Line {
+    fn new(text: String, width: usize) -> Line {
+        Line { text, width }
+    }
+
+    /// A line of unstyled text, with `style` applied to all of it.
+    fn styled(text: &str, style: impl Fn(&str) -> colored::ColoredString) -> Line {
+        Line::new(style(text).to_string(), text.width())
+    }
+}
+
+/// A run of inline text that shares the same marks.
+#[derive(Debug)]
+struct Span {
+    text: String,
+    marks: Vec<Mark>,
+}
+
+impl Span {
+    fn new(text: &str, marks: Vec<Mark>) -> Span {
+        Span {
+            text: text.to_owned(),
+            marks,
+        }
+    }
+}
+
+fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn apply_marks(text: &str, marks: &[Mark]) -> String {
+    let mut styled = text.normal();
+    let mut href = None;
+
+    for mark in marks {
+        styled = match mark {
+            Mark::Strong => styled.bold(),
+            Mark::Em => styled.italic(),
+            Mark::Strike => styled.strikethrough(),
+            Mark::Underline => styled.underline(),
+            Mark::Code => styled.bright_yellow(),
+            Mark::TextColor { color } => match parse_hex_color(color) {
+                Some((r, g, b)) => styled.truecolor(r, g, b),
+                None => styled,
+            },
+            Mark::Link { href: h, .. } => {
+                href = Some(h);
+                styled.bright_blue().underline()
+            }
+            Mark::Subsup { .. } => styled,
+        };
+    }
+
+    match href {
+        // OSC 8 makes the text clickable in terminals that support it, and is ignored by
+        // the rest
+        Some(href) if colored::control::SHOULD_COLORIZE.should_colorize() => {
+            format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", href, styled)
+        }
+        _ => styled.to_string(),
+    }
+}
+
+// Flatten inline content (or the inline content of blocks, e.g. inside a table cell)
+// into spans.
+fn inline_spans(content: &[Node], marks: &[Mark], spans: &mut Vec<Span>) {
+    for node in content {
+        match node {
+            Node::InlineNode {
+                nodetype,
+                text,
+                marks: own_marks,
+            } => {
+                let mut node_marks = marks.to_vec();
+                node_marks.extend(own_marks.iter().flatten().cloned());
+
+                match nodetype {
+                    InlineNodeType::Text => {
+                        spans.push(Span::new(text.as_deref().unwrap_or_default(), node_marks))
+                    }
+                    InlineNodeType::HardBreak => spans.push(Span::new("\n", vec![])),
+                    InlineNodeType::Emoji {
+                        short_name, text, ..
+                    } => spans.push(Span::new(text.as_deref().unwrap_or(short_name), node_marks)),
+                    InlineNodeType::Mention { id, text, .. } => {
+                        let text = text.to_owned().unwrap_or_else(|| format!("@{}", id));
+                        node_marks.push(Mark::Strong);
+                        spans.push(Span::new(&text, node_marks));
+                    }
+                    InlineNodeType::InlineCard { url } => {
+                        node_marks.push(Mark::Link {
+                            href: url.to_owned(),
+                            title: None,
+                        });
+                        spans.push(Span::new(url, node_marks));
+                    }
+                }
+            }
+            Node::BlockNode { content, .. } | Node::Root { content, .. } => {
+                if !spans.is_empty() {
+                    spans.push(Span::new("\n", vec![]));
+                }
+                inline_spans(content, marks, spans);
+            }
+            Node::Unknown(value) => spans.push(Span::new(
+                &convert::unknown_node_text(value),
+                marks.to_vec(),
+            )),
+        }
+    }
+}
+
+enum Token {
+    Word(Line),
+    Space,
+    Break,
+}
+
+fn tokenize(spans: &[Span]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = Line::default();
+
+    for span in spans {
+        let mut piece = String::new();
+        for c in span.text.chars() {
+            if c.is_whitespace() {
+                if !piece.is_empty() {
+                    word.text.push_str(&apply_marks(&piece, &span.marks));
+                    word.width += piece.width();
+                    piece.clear();
+                }
+                if !word.text.is_empty() {
+                    tokens.push(Token::Word(std::mem::take(&mut word)));
+                }
+                tokens.push(if c == '\n' {
+                    Token::Break
+                } else {
+                    Token::Space
+                });
+            } else {
+                piece.push(c);
+            }
+        }
+        if !piece.is_empty() {
+            word.text.push_str(&apply_marks(&piece, &span.marks));
+            word.width += piece.width();
+        }
+    }
+    if !word.text.is_empty() {
+        tokens.push(Token::Word(word));
+    }
+
+    tokens
+}
+
+/// Wrap spans to `width` columns, breaking lines between words. Words longer than a
+/// line get a line of their own.
+fn wrap(spans: &[Span], width: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    let mut space = false;
+
+    for token in tokenize(spans) {
+        match token {
+            Token::Space => space = line.width > 0,
+            Token::Break => {
+                lines.push(std::mem::take(&mut line));
+                space = false;
+            }
+            Token::Word(word) => {
+                let needed = word.width + if space { 1 } else { 0 };
+                if line.width > 0 && line.width + needed > width {
+                    lines.push(std::mem::take(&mut line));
+                    space = false;
+                }
+                if space {
+                    line.text.push(' ');
+                    line.width += 1;
+                    space = false;
+                }
+                line.text.push_str(&word.text);
+                line.width += word.width;
+            }
+        }
+    }
+    if line.width > 0 || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn prefix_lines(lines: Vec<Line>, first: &Line, rest: &Line) -> Vec<Line> {
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            Line::new(
+                format!("{}{}", prefix.text, line.text),
+                prefix.width + line.width,
+            )
+        })
+        .collect()
+}
+
+// Render blocks one after another, separated by blank lines unless `tight`.
+fn render_blocks(content: &[Node], width: usize, depth: usize, tight: bool) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for block in content {
+        let mut block_lines = render_block(block, width, depth);
+        if block_lines.iter().all(|line| line.text.is_empty()) {
+            continue;
+        }
+        if !tight && !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.append(&mut block_lines);
+    }
+    lines
+}
+
+fn render_list(
+    items: &[Node],
+    width: usize,
+    depth: usize,
+    marker: impl Fn(usize) -> String,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let marker = marker(i);
+        let marker_width = marker.width() + 1;
+        let item_lines = match item {
+            Node::BlockNode { content, .. } => {
+                render_blocks(content, width.saturating_sub(marker_width), depth + 1, true)
+            }
+            node => render_block(node, width.saturating_sub(marker_width), depth + 1),
+        };
+        lines.append(&mut prefix_lines(
+            item_lines,
+            &Line::new(format!("{} ", marker.dimmed()), marker_width),
+            &Line::new(" ".repeat(marker_width), marker_width),
+        ));
+    }
+    lines
+}
+
+fn panel_color(panel_type: &str) -> colored::Color {
+    match panel_type {
+        "note" => colored::Color::Magenta,
+        "warning" => colored::Color::Yellow,
+        "success" => colored::Color::Green,
+        "error" => colored::Color::Red,
+        _ => colored::Color::Blue,
+    }
+}
+
+fn render_block(node: &Node, width: usize, depth: usize) -> Vec<Line> {
+    let (nodetype, content) = match node {
+        Node::Root { content, .. } => return render_blocks(content, width, depth, false),
+        Node::BlockNode { nodetype, content } => (nodetype, content),
+        node => {
+            let mut spans = Vec::new();
+            inline_spans(std::slice::from_ref(node), &[], &mut spans);
+            return wrap(&spans, width);
+        }
+    };
+
+    match nodetype {
+        BlockNodeType::Paragraph => {
+            let mut spans = Vec::new();
+            inline_spans(content, &[], &mut spans);
+            wrap(&spans, width)
+        }
+        BlockNodeType::Heading { level } => {
+            let mut marks = vec![Mark::Strong];
+            if *level <= 2 {
+                marks.push(Mark::Underline);
+            }
+            let mut spans = Vec::new();
+            inline_spans(content, &marks, &mut spans);
+            wrap(&spans, width)
+        }
+        BlockNodeType::BulletList => render_list(content, width, depth, |_| {
+            ["•", "◦", "▪"][depth % 3].to_owned()
+        }),
+        BlockNodeType::OrderedList => {
+            let marker_width = content.len().to_string().len() + 1;
+            render_list(content, width, depth, |i| {
+                format!("{:>width$}", format!("{}.", i + 1), width = marker_width)
+            })
+        }
+        BlockNodeType::ListItem => render_blocks(content, width, depth, true),
+        BlockNodeType::CodeBlock { language } => render_code(content, language.as_deref()),
+        BlockNodeType::BlockQuote => prefix_lines(
+            render_blocks(content, width.saturating_sub(2), depth, false),
+            &Line::styled("│ ", |s| s.dimmed()),
+            &Line::styled("│ ", |s| s.dimmed()),
+        ),
+        BlockNodeType::Panel { panel_type } => {
+            let color = panel_color(panel_type);
+            let bar = Line::styled("▌ ", |s| s.color(color));
+            prefix_lines(
+                render_blocks(content, width.saturating_sub(2), depth, false),
+                &bar,
+                &bar,
+            )
+        }
+        BlockNodeType::Rule => vec![Line::styled(&"─".repeat(width), |s| s.dimmed())],
+        BlockNodeType::Table { .. } => render_table(content, width),
+        BlockNodeType::TableRow | BlockNodeType::TableCell { .. } | BlockNodeType::TableHeader => {
+            render_blocks(content, width, depth, false)
+        }
+        BlockNodeType::MediaGroup | BlockNodeType::MediaSingle | BlockNodeType::Media => {
+            vec![Line::styled("[attachment]", |s| s.dimmed())]
+        }
+    }
+}
+
+fn render_code(content: &[Node], language: Option<&str>) -> Vec<Line> {
+    let code = content
+        .iter()
+        .map(|node| match node {
+            Node::InlineNode { text, .. } => text.to_owned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .collect::<String>()
+        .replace('\t', "    ");
+
+    let mut lines = Vec::new();
+    if let Some(language) = language {
+        lines.push(Line::styled(language, |s| s.dimmed().italic()));
+    }
+    // Code isn't wrapped; overlong lines are left to the terminal
+    for line in code.lines() {
+        lines.push(Line::new(
+            format!("{} {}", "│".dimmed(), highlight_code(line, language)),
+            line.width() + 2,
+        ));
+    }
+    lines
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "False", "finally", "for", "from", "if", "import", "in", "is", "lambda",
+    "None", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+];
+
+const JS_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "delete",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "of",
+    "return",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "undefined",
+    "var",
+    "while",
+    "yield",
+];
+
+const C_LIKE_KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "else",
+    "enum",
+    "extension",
+    "extends",
+    "false",
+    "final",
+    "for",
+    "func",
+    "fun",
+    "go",
+    "guard",
+    "if",
+    "implements",
+    "import",
+    "interface",
+    "let",
+    "nil",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "throws",
+    "true",
+    "try",
+    "val",
+    "var",
+    "void",
+    "while",
+];
+
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+    "local", "then", "while",
+];
+
+fn keywords(language: &str) -> &'static [&'static str] {
+    match language {
+        "rust" | "rs" => RUST_KEYWORDS,
+        "python" | "py" => PYTHON_KEYWORDS,
+        "javascript" | "js" | "typescript" | "ts" | "jsx" | "tsx" => JS_KEYWORDS,
+        "c" | "cpp" | "c++" | "csharp" | "c#" | "go" | "java" | "kotlin" | "swift"
+        | "objective-c" | "objc" | "dart" | "scala" => C_LIKE_KEYWORDS,
+        "sh" | "bash" | "shell" | "zsh" => SHELL_KEYWORDS,
+        _ => &[],
+    }
+}
+
+/// Highlight a line of code: keywords (for languages we know), strings, numbers and line
+/// comments. Block comments and multi-line strings aren't tracked across lines.
+fn highlight_code(line: &str, language: Option<&str>) -> String {
+    let language = language.unwrap_or_default().to_lowercase();
+    let keywords = keywords(&language);
+    let hash_comments = matches!(
+        language.as_str(),
+        "python" | "py" | "sh" | "bash" | "shell" | "zsh" | "ruby" | "rb" | "yaml" | "yml" | "toml"
+    );
+    // Single quotes start lifetimes and chars in Rust, not strings
+    let single_quote_strings = !matches!(language.as_str(), "rust" | "rs");
+
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest = chars[i..].iter().collect::<String>();
+
+        if (hash_comments && c == '#') || (!hash_comments && rest.starts_with("//")) {
+            out.push_str(&rest.dimmed().to_string());
+            break;
+        } else if c == '"' || (c == '\'' && single_quote_strings) {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != c {
+                if chars[end] == '\\' {
+                    end += 1;
+                }
+                end += 1;
+            }
+            let end = (end + 1).min(chars.len());
+            let string = chars[i..end].iter().collect::<String>();
+            out.push_str(&string.green().to_string());
+            i = end;
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = i;
+            while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+            let word = chars[i..end].iter().collect::<String>();
+            if c.is_ascii_digit() {
+                out.push_str(&word.bright_magenta().to_string());
+            } else if keywords.contains(&word.as_str()) {
+                out.push_str(&word.bright_blue().bold().to_string());
+            } else {
+                out.push_str(&word);
+            }
+            i = end;
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+
+    out
+}
+
+fn cell_spans(cell: &Node) -> (Vec<Span>, bool) {
+    let mut spans = Vec::new();
+    match cell {
+        Node::BlockNode {
+            nodetype: BlockNodeType::TableHeader,
+            content,
+        } => {
+            inline_spans(content, &[Mark::Strong], &mut spans);
+            (spans, true)
+        }
+        Node::BlockNode { content, .. } => {
+            inline_spans(content, &[], &mut spans);
+            (spans, false)
+        }
+        cell => {
+            inline_spans(std::slice::from_ref(cell), &[], &mut spans);
+            (spans, false)
+        }
+    }
+}
+
+// Shrink the widest columns until the table fits, leaving each at least 3 wide.
+fn fit_columns(mut widths: Vec<usize>, available: usize) -> Vec<usize> {
+    while widths.iter().sum::<usize>() > available {
+        let (widest, width) = widths
+            .iter()
+            .cloned()
+            .enumerate()
+            .max_by_key(|&(_, w)| w)
+            .unwrap_or_default();
+        if width <= 3 {
+            break;
+        }
+        widths[widest] -= 1;
+    }
+    widths
+}
+
+fn table_border(widths: &[usize], left: &str, middle: &str, right: &str) -> Line {
+    let segments = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+    let border = format!("{}{}{}", left, segments.join(middle), right);
+    Line::styled(&border, |s| s.dimmed())
+}
+
+fn render_table(rows: &[Node], width: usize) -> Vec<Line> {
+    let rows = rows
+        .iter()
+        .map(|row| match row {
+            Node::BlockNode { content, .. } => content.iter().map(cell_spans).collect(),
+            _ => Vec::new(),
+        })
+        .collect::<Vec<Vec<_>>>();
+
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    if columns == 0 {
+        return Vec::new();
+    }
+
+    let mut natural = vec![1; columns];
+    for row in &rows {
+        for (i, (spans, _)) in row.iter().enumerate() {
+            let cell_width = wrap(spans, usize::MAX)
+                .iter()
+                .map(|l| l.width)
+                .max()
+                .unwrap_or(0);
+            natural[i] = natural[i].max(cell_width);
+        }
+    }
+    // Each column has a border and a space either side, plus the border on the right
+    let widths = fit_columns(natural, width.saturating_sub(3 * columns + 1));
+
+    let bar = "│".dimmed();
+    let mut lines = vec![table_border(&widths, "┌", "┬", "┐")];
+    for (r, row) in rows.iter().enumerate() {
+        let cells = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| match row.get(i) {
+                Some((spans, _)) => wrap(spans, w),
+                None => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(|c| c.len()).max().unwrap_or(1);
+
+        for l in 0..height {
+            let mut text = bar.to_string();
+            let mut line_width = 1;
+            for (cell, &w) in cells.iter().zip(&widths) {
+                let (cell_text, cell_width) = match cell.get(l) {
+                    Some(line) => (line.text.as_str(), line.width),
+                    None => ("", 0),
+                };
+                let padding = " ".repeat(w.saturating_sub(cell_width));
+                text.push_str(&format!(" {}{} {}", cell_text, padding, bar));
+                line_width += cell_width.max(w) + 3;
+            }
+            lines.push(Line::new(text, line_width));
+        }
+
+        let is_header = !row.is_empty() && row.iter().all(|(_, header)| *header);
+        if is_header && r + 1 < rows.len() {
+            lines.push(table_border(&widths, "├", "┼", "┤"));
+        }
+    }
+    lines.push(table_border(&widths, "└", "┴", "┘"));
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(json: &str, width: usize) -> String {
+        colored::control::set_override(false);
+        adf_to_terminal(&serde_json::from_str(json).unwrap(), width)
+    }
+
+    #[test]
+    fn test_wraps_to_width() {
+        let rendered = render(
+            r#"{"type": "bulletList", "content": [{"type": "listItem", "content": [
+                {"type": "paragraph", "content": [{"type": "text", "text": "one two three four five"}]}
+            ]}]}"#,
+            12,
+        );
+        assert_eq!(rendered, "• one two\n  three four\n  five");
+    }
+
+    #[test]
+    fn test_table_columns_line_up() {
+        let rendered = render(
+            r#"{"type": "table", "attrs": {}, "content": [
+                {"type": "tableRow", "content": [
+                    {"type": "tableHeader", "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Key"}]}]},
+                    {"type": "tableHeader", "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Summary"}]}]}
+                ]},
+                {"type": "tableRow", "content": [
+                    {"type": "tableCell", "content": [{"type": "paragraph", "content": [{"type": "text", "text": "ABC-1"}]}]},
+                    {"type": "tableCell", "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Crashes on launch"}]}]}
+                ]}
+            ]}"#,
+            22,
+        );
+        assert_eq!(
+            rendered,
+            "┌───────┬────────────┐\n\
+             │ Key   │ Summary    │\n\
+             ├───────┼────────────┤\n\
+             │ ABC-1 │ Crashes on │\n\
+             │       │ launch     │\n\
+             └───────┴────────────┘"
+        );
+    }
+
+    #[test]
+    fn test_links_are_plain_without_colour() {
+        let rendered = render(
+            r#"{"type": "paragraph", "content": [{"type": "text", "text": "docs",
+                "marks": [{"type": "link", "attrs": {"href": "https://example.com"}}]}]}"#,
+            80,
+        );
+        assert_eq!(rendered, "docs");
+    }
+}
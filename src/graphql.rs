@@ -2,13 +2,11 @@
 //! This looks to be the only way to fetch a list of PRs associated with an issue.
 
 use heck::TitleCase;
-use reqwest::{Client, Method, StatusCode};
+use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use std::error::Error;
-
-use super::{model, ApiConfig, ApiError};
+use super::{model, Error, JiraClient, Result};
 
 static ISSUE_PR_GRAPHQL: &'static str = include_str!("../graphql/issue_prs.graphql");
 
@@ -94,61 +92,56 @@ struct Response {
     data: ResponseData,
 }
 
-pub async fn get_issue_pull_requests(
-    issue: &model::IssueSearchResult,
-    config: &ApiConfig,
-) -> Result<Option<Vec<PullRequest>>, Box<dyn Error>> {
-    let request = GetIssuePrsRequest {
-        operation_name: "DevDetailsDialog".to_owned(),
-        // TODO: Don't copy this every time
-        query: ISSUE_PR_GRAPHQL.to_owned(),
-        variables: GetIssuePrsRequestVariables {
-            issue_id: issue.id.to_owned(),
-        },
-    };
-
-    let request = Client::new()
-        .request(
-            Method::POST,
-            &format!("https://{}.atlassian.net/jsw/graphql", &config.subdomain),
-        )
-        .query(&[("operation", "DevDetailsDialog")])
-        .json(&request)
-        .basic_auth(&config.email, Some(&config.token));
-
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::OK => {
-            let result = response.json::<Response>().await?;
-            let mut pull_requests: Vec<PullRequest> = Vec::new();
-
-            // Ugh
-            let instance = result
-                .data
-                .development_information
-                .details
-                .instance_types
-                .first();
-
-            if let Some(instance) = instance {
-                let repo = instance.repository.first().unwrap();
-                for branch in &repo.branches {
-                    for pr in &branch.pull_requests {
-                        // TODO: Don't clone
-                        pull_requests.push(pr.clone());
+impl JiraClient {
+    pub async fn get_issue_pull_requests(
+        &self,
+        issue: &model::IssueSearchResult,
+    ) -> Result<Option<Vec<PullRequest>>> {
+        let request = GetIssuePrsRequest {
+            operation_name: "DevDetailsDialog".to_owned(),
+            // TODO: Don't copy this every time
+            query: ISSUE_PR_GRAPHQL.to_owned(),
+            variables: GetIssuePrsRequestVariables {
+                issue_id: issue.id.to_owned(),
+            },
+        };
+
+        let request = self
+            .request_url(&format!("{}/jsw/graphql", self.base_url), Method::POST)
+            .query(&[("operation", "DevDetailsDialog")])
+            .json(&request);
+
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let result = response.json::<Response>().await?;
+                let mut pull_requests: Vec<PullRequest> = Vec::new();
+
+                // Ugh
+                let repo = result
+                    .data
+                    .development_information
+                    .details
+                    .instance_types
+                    .first()
+                    .and_then(|instance| instance.repository.first());
+
+                // An instance can be linked without any repositories
+                match repo {
+                    Some(repo) => {
+                        for branch in &repo.branches {
+                            for pr in &branch.pull_requests {
+                                // TODO: Don't clone
+                                pull_requests.push(pr.clone());
+                            }
+                        }
+                        Ok(Some(pull_requests))
                     }
+                    None => Ok(None),
                 }
-
-                Ok(Some(pull_requests))
-            } else {
-                Ok(None)
             }
+            _ => Err(Error::from_response(response).await),
         }
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to fetch issue, {}",
-            code,
-            response.text().await?
-        )))),
     }
 }
@@ -1,45 +1,68 @@
+mod cache;
+pub mod config;
 pub mod convert;
+pub mod drafts;
+mod error;
 pub mod format;
 pub mod graphql;
 pub mod model;
+pub mod output;
 pub mod search;
+pub mod sort;
 pub mod util;
 
+use reqwest::multipart::{Form, Part};
 use reqwest::{Client, Method, RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::error::Error;
-use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[derive(Debug)]
-struct ApiError {
-    message: String,
+pub use error::{Error, Result};
+
+/// How many comments `get_issue` includes.
+const RECENT_COMMENTS: usize = 5;
+
+/// How long the list of fields is cached for. Run `jira fields --refresh` after adding one.
+const FIELDS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Debug)]
+struct AssignIssueRequest {
+    /// `None` unassigns the issue.
+    #[serde(rename = "accountId")]
+    account_id: Option<String>,
 }
 
-impl ApiError {
-    fn new(msg: &str) -> ApiError {
-        ApiError {
-            message: msg.to_string(),
-        }
-    }
+#[derive(Serialize, Debug)]
+struct UpdateIssueRequest {
+    fields: model::IssueUpdate,
 }
 
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
-    }
+#[derive(Serialize, Debug)]
+struct CommentRequest {
+    body: convert::Node,
 }
 
-impl Error for ApiError {
-    fn description(&self) -> &str {
-        &self.message
-    }
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LinkTypesResponse {
+    issue_link_types: Vec<model::IssueLinkType>,
 }
 
 #[derive(Serialize, Debug)]
-struct AssignIssueRequest {
-    #[serde(rename = "accountId")]
-    account_id: String,
+struct IssueKeyRef {
+    key: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LinkIssuesRequest {
+    #[serde(rename = "type")]
+    link_type: model::IssueLinkType,
+    inward_issue: IssueKeyRef,
+    outward_issue: IssueKeyRef,
 }
 
 #[derive(Serialize, Debug)]
@@ -47,6 +70,11 @@ struct TransitionIssueRequest {
     transition: model::IssueTransition,
 }
 
+#[derive(Deserialize, Debug)]
+struct TransitionsResponse {
+    transitions: Vec<model::Transition>,
+}
+
 #[derive(Serialize, Debug)]
 struct CreateIssueRequest {
     fields: model::Issue,
@@ -54,11 +82,11 @@ struct CreateIssueRequest {
 }
 
 #[derive(Deserialize, Debug)]
-struct CreateIssueResponse {
-    id: String,
-    key: String,
+pub struct CreateIssueResponse {
+    pub id: String,
+    pub key: String,
     #[serde(rename = "self")]
-    url: String,
+    pub url: String,
 }
 
 pub struct ApiConfig {
@@ -66,186 +94,734 @@ pub struct ApiConfig {
     pub token: String,
     pub subdomain: String,
     pub project: String,
+    /// The root URL of the Jira instance (e.g. a self-hosted Data Center install or a
+    /// local stub). Falls back to `https://<subdomain>.atlassian.net` when unset.
+    pub base_url: Option<String>,
+    /// Scoping for the built-in issue lists, and defaults for new issues.
+    pub profile: config::ProjectProfile,
 }
 
-fn build_request(path: &str, method: Method, config: &ApiConfig) -> RequestBuilder {
-    Client::new()
-        .request(
-            method,
-            &format!(
-                "https://{}.atlassian.net/rest/api/3/{}",
-                &config.subdomain, path
-            ),
-        )
-        .basic_auth(&config.email, Some(&config.token))
+impl ApiConfig {
+    pub fn base_url(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => base_url.trim_end_matches('/').to_owned(),
+            None => format!("https://{}.atlassian.net", self.subdomain),
+        }
+    }
 }
 
-pub async fn update_issue_status(
-    issue_key: &str,
-    transition: model::IssueTransition,
-    config: &ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let request = TransitionIssueRequest { transition };
-
-    let request = build_request(
-        &format!("/issue/{}/transitions", issue_key),
-        Method::POST,
-        &config,
-    )
-    .json(&request);
-
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::NO_CONTENT => Ok(()),
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to transition issue status, {}",
-            code,
-            response.text().await?
-        )))),
-    }
+/// A Jira API client. This owns a single (pooled) HTTP client, so prefer
+/// creating one `JiraClient` and reusing it for every request.
+pub struct JiraClient {
+    http: Client,
+    config: ApiConfig,
+    base_url: String,
+    /// Fetched at most once per run; see `get_fields`.
+    fields: Mutex<Option<Arc<model::Fields>>>,
 }
 
-pub async fn get_myself(config: &ApiConfig) -> Result<model::User, Box<dyn Error>> {
-    let request = build_request("/myself", Method::GET, &config);
-    let response = request.send().await?;
+impl JiraClient {
+    /// Create a client that talks to the instance at `config.base_url()`.
+    pub fn new(config: ApiConfig) -> JiraClient {
+        JiraClient {
+            http: Client::new(),
+            base_url: config.base_url(),
+            config,
+            fields: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &ApiConfig {
+        &self.config
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The URL to view an issue in a browser.
+    pub fn browse_url(&self, issue_key: &str) -> String {
+        format!("{}/browse/{}", self.base_url, issue_key)
+    }
+
+    /// The URL to view a comment in a browser.
+    pub fn comment_url(&self, issue_key: &str, id: &str) -> String {
+        format!("{}?focusedCommentId={}", self.browse_url(issue_key), id)
+    }
+
+    /// Build a request against the v3 REST API; `path` is relative to `/rest/api/3`.
+    fn request(&self, path: &str, method: Method) -> RequestBuilder {
+        let url = format!(
+            "{}/rest/api/3/{}",
+            self.base_url,
+            path.trim_start_matches('/')
+        );
+        self.request_url(&url, method)
+    }
+
+    /// Build an authenticated request against an absolute URL.
+    fn request_url(&self, url: &str, method: Method) -> RequestBuilder {
+        self.http
+            .request(method, url)
+            .basic_auth(&self.config.email, Some(&self.config.token))
+    }
+
+    pub async fn update_issue_status(
+        &self,
+        issue_key: &str,
+        transition: model::IssueTransition,
+    ) -> Result<()> {
+        let request = TransitionIssueRequest { transition };
 
-    match response.status() {
-        StatusCode::OK => {
-            let user = response.json::<model::User>().await?;
-            Ok(user)
+        let request = self
+            .request(&format!("/issue/{}/transitions", issue_key), Method::POST)
+            .json(&request);
+
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
         }
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to fetch myself, {}",
-            code,
-            response.text().await?
-        )))),
     }
-}
 
-pub async fn assign_issue_to_myself(
-    issue_key: &str,
-    config: &ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let user = get_myself(&config).await?;
-
-    let request = AssignIssueRequest {
-        account_id: user.account_id,
-    };
-
-    let request = build_request(
-        &format!("/issue/{}/assignee", issue_key),
-        Method::PUT,
-        &config,
-    )
-    .json(&request);
-
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::NO_CONTENT => Ok(()),
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to assign issue to myself, {}",
-            code,
-            response.text().await?
-        )))),
+    /// The transitions currently available for an issue, given its status and workflow.
+    pub async fn get_transitions(&self, issue_key: &str) -> Result<Vec<model::Transition>> {
+        let request = self.request(&format!("/issue/{}/transitions", issue_key), Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let transitions = response.json::<TransitionsResponse>().await?;
+                Ok(transitions.transitions)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
     }
-}
 
-pub async fn create_issue(issue: model::Issue, config: &ApiConfig) -> Result<(), Box<dyn Error>> {
-    let request = CreateIssueRequest {
-        fields: issue,
-        update: HashMap::new(),
-    };
-
-    let request = build_request("/issue", Method::POST, &config).json(&request);
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::CREATED => {
-            let created = response.json::<CreateIssueResponse>().await?;
-            println!(
-                "https://{}.atlassian.net/browse/{}",
-                config.subdomain, created.key
-            );
-            Ok(())
-        }
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to create an issue, {}",
-            code,
-            response.text().await?
-        )))),
+    /// Transition an issue by transition name or target status name (case-insensitively),
+    /// returning the transition that was used.
+    pub async fn transition_issue(&self, issue_key: &str, name: &str) -> Result<model::Transition> {
+        let transitions = self.get_transitions(issue_key).await?;
+
+        let transition = match model::find_transition(&transitions, name) {
+            Some(transition) => transition,
+            None => {
+                return Err(Error::UnknownTransition {
+                    requested: name.to_owned(),
+                    available: transitions
+                        .iter()
+                        .map(|t| format!("{} (-> {})", t.name, t.to.name))
+                        .collect(),
+                })
+            }
+        };
+
+        self.update_issue_status(issue_key, transition.into())
+            .await?;
+        Ok(transition.clone())
     }
-}
 
-pub async fn get_issue(
-    issue_key: &str,
-    config: &ApiConfig,
-) -> Result<model::IssueSearchResult, Box<dyn Error>> {
-    let result = get_issue_simple(issue_key, config).await?;
-
-    // Enrich issue with PRs
-    let pull_requests = graphql::get_issue_pull_requests(&result, config).await?;
-    let result = model::IssueSearchResult {
-        pull_requests,
-        ..result
-    };
-
-    // Enrich issue with subtasks
-    let subtasks = search::issue_subtasks(config, issue_key).await?;
-    let subtasks = Some(subtasks);
-    let result = model::IssueSearchResult { subtasks, ..result };
-
-    // Enrich issue with child issues if this issue is an epic
-    let result = if result.fields.issuetype.name == "Epic" {
-        let epic_issues = Some(search::epic_issues(&config, &result).await?);
-        model::IssueSearchResult {
-            epic_issues,
-            ..result
+    /// The kinds of links this Jira instance supports.
+    pub async fn get_link_types(&self) -> Result<Vec<model::IssueLinkType>> {
+        let request = self.request("/issueLinkType", Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let link_types = response.json::<LinkTypesResponse>().await?;
+                Ok(link_types.issue_link_types)
+            }
+            _ => Err(Error::from_response(response).await),
         }
-    } else {
-        result
-    };
-
-    // Enrich issue with parent epic if this is part of an epic
-    let result = if let Some(epic) = &result.fields.epic {
-        match epic {
-            model::IssueEpic::Key(k) => model::IssueSearchResult {
-                fields: model::Issue {
-                    epic: Some(model::IssueEpic::Full(Box::new(
-                        get_issue_simple(&k, &config).await?,
-                    ))),
-                    ..result.fields
-                },
-                ..result
+    }
+
+    /// Link two issues so that they read "`issue_key` `relation` `other_key`", e.g.
+    /// "ABC-1 blocks ABC-2" or "ABC-1 is blocked by ABC-2". Returns the link type used, and
+    /// which way round it reads.
+    pub async fn link_issues(
+        &self,
+        issue_key: &str,
+        relation: &str,
+        other_key: &str,
+    ) -> Result<(model::IssueLinkType, model::LinkDirection)> {
+        let link_types = self.get_link_types().await?;
+        let (link_type, direction) = match model::find_link_type(&link_types, relation) {
+            Some((link_type, direction)) => (link_type.clone(), direction),
+            None => {
+                let mut available: Vec<String> = Vec::new();
+                for t in &link_types {
+                    for relation in [&t.outward, &t.inward].iter() {
+                        if !available.contains(relation) {
+                            available.push(relation.to_string());
+                        }
+                    }
+                }
+                return Err(Error::UnknownLinkType {
+                    requested: relation.to_owned(),
+                    available,
+                });
+            }
+        };
+
+        // Jira reads links as "<inward issue> <outward description> <outward issue>"
+        let (inward, outward) = match direction {
+            model::LinkDirection::Outward => (issue_key, other_key),
+            model::LinkDirection::Inward => (other_key, issue_key),
+        };
+        let request = LinkIssuesRequest {
+            link_type: model::IssueLinkType {
+                name: link_type.name.to_owned(),
+                ..Default::default()
             },
-            model::IssueEpic::Full(_) => result,
+            inward_issue: IssueKeyRef {
+                key: inward.to_owned(),
+            },
+            outward_issue: IssueKeyRef {
+                key: outward.to_owned(),
+            },
+        };
+
+        let request = self.request("/issueLink", Method::POST).json(&request);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::CREATED => Ok((link_type, direction)),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn delete_link(&self, link_id: &str) -> Result<()> {
+        let request = self.request(&format!("/issueLink/{}", link_id), Method::DELETE);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn get_myself(&self) -> Result<model::User> {
+        let request = self.request("/myself", Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let user = response.json::<model::User>().await?;
+                Ok(user)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Users whose name or email address matches `query`.
+    pub async fn find_users(&self, query: &str) -> Result<Vec<model::User>> {
+        let request = self
+            .request("/user/search", Method::GET)
+            .query(&[("query", query)]);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let users = response.json::<Vec<model::User>>().await?;
+                Ok(users)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Users matching `query` who can be assigned `issue_key`, or (without an issue key)
+    /// issues in the current project.
+    pub async fn find_assignable_users(
+        &self,
+        query: &str,
+        issue_key: Option<&str>,
+    ) -> Result<Vec<model::User>> {
+        let scope = match issue_key {
+            Some(issue_key) => ("issueKey", issue_key),
+            None => ("project", self.config.project.as_str()),
+        };
+        let request = self
+            .request("/user/assignable/search", Method::GET)
+            .query(&[("query", query), scope]);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let users = response.json::<Vec<model::User>>().await?;
+                Ok(users)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Assign an issue to the current user, and return them.
+    pub async fn assign_issue_to_myself(&self, issue_key: &str) -> Result<model::User> {
+        let user = self.get_myself().await?;
+        self.assign_issue(issue_key, Some(&user.account_id)).await?;
+        Ok(user)
+    }
+
+    /// Assign an issue to the user with `account_id`, or unassign it if that's `None`.
+    pub async fn assign_issue(&self, issue_key: &str, account_id: Option<&str>) -> Result<()> {
+        let request = AssignIssueRequest {
+            account_id: account_id.map(String::from),
+        };
+
+        let request = self
+            .request(&format!("/issue/{}/assignee", issue_key), Method::PUT)
+            .json(&request);
+
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn create_issue(&self, mut issue: model::Issue) -> Result<CreateIssueResponse> {
+        if let Some(model::IssueEpic::Key(epic)) = issue.epic.take() {
+            match self.get_fields().await?.epic_link() {
+                Some(id) => {
+                    issue
+                        .custom_fields
+                        .insert(id.to_owned(), serde_json::Value::String(epic));
+                }
+                // Team-managed projects file issues under epics as their parent
+                None if issue.parent.is_none() => {
+                    issue.parent = Some(model::IssueParent {
+                        key: epic,
+                        ..Default::default()
+                    })
+                }
+                None => {}
+            }
+        }
+
+        let request = CreateIssueRequest {
+            fields: issue,
+            update: HashMap::new(),
+        };
+
+        let request = self.request("/issue", Method::POST).json(&request);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::CREATED => {
+                let created = response.json::<CreateIssueResponse>().await?;
+                Ok(created)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Change some of an issue's fields. Does nothing if `update` is empty.
+    pub async fn update_issue(&self, issue_key: &str, update: model::IssueUpdate) -> Result<()> {
+        if update.is_empty() {
+            return Ok(());
+        }
+
+        let request = UpdateIssueRequest { fields: update };
+        let request = self
+            .request(&format!("/issue/{}", issue_key), Method::PUT)
+            .json(&request);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    async fn comments_page(
+        &self,
+        issue_key: &str,
+        start_at: usize,
+        max_results: usize,
+        order_by: &str,
+    ) -> Result<model::CommentPage> {
+        let request = self
+            .request(&format!("/issue/{}/comment", issue_key), Method::GET)
+            .query(&[
+                ("startAt", start_at.to_string()),
+                ("maxResults", max_results.to_string()),
+                ("orderBy", order_by.to_owned()),
+            ]);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let page = response.json::<model::CommentPage>().await?;
+                Ok(page)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Every comment on an issue, oldest first.
+    pub async fn get_comments(&self, issue_key: &str) -> Result<Vec<model::Comment>> {
+        let mut comments = Vec::new();
+        loop {
+            let mut page = self
+                .comments_page(issue_key, comments.len(), 100, "created")
+                .await?;
+            let page_was_empty = page.comments.is_empty();
+            comments.append(&mut page.comments);
+
+            if page_was_empty || comments.len() >= page.total {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    /// The `limit` most recent comments on an issue, oldest first. The page's `total`
+    /// counts every comment.
+    pub async fn recent_comments(
+        &self,
+        issue_key: &str,
+        limit: usize,
+    ) -> Result<model::CommentPage> {
+        let mut page = self.comments_page(issue_key, 0, limit, "-created").await?;
+        page.comments.reverse();
+        Ok(page)
+    }
+
+    pub async fn get_comment(&self, issue_key: &str, id: &str) -> Result<model::Comment> {
+        let request = self.request(&format!("/issue/{}/comment/{}", issue_key, id), Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let comment = response.json::<model::Comment>().await?;
+                Ok(comment)
+            }
+            _ => Err(Error::from_response(response).await),
         }
-    } else {
-        result
-    };
+    }
+
+    pub async fn add_comment(
+        &self,
+        issue_key: &str,
+        body: convert::Node,
+    ) -> Result<model::Comment> {
+        let request = self
+            .request(&format!("/issue/{}/comment", issue_key), Method::POST)
+            .json(&CommentRequest { body });
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::CREATED => {
+                let comment = response.json::<model::Comment>().await?;
+                Ok(comment)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Replace the body of a comment.
+    pub async fn update_comment(
+        &self,
+        issue_key: &str,
+        id: &str,
+        body: convert::Node,
+    ) -> Result<model::Comment> {
+        let request = self
+            .request(&format!("/issue/{}/comment/{}", issue_key, id), Method::PUT)
+            .json(&CommentRequest { body });
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let comment = response.json::<model::Comment>().await?;
+                Ok(comment)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn delete_comment(&self, issue_key: &str, id: &str) -> Result<()> {
+        let request = self.request(
+            &format!("/issue/{}/comment/{}", issue_key, id),
+            Method::DELETE,
+        );
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
 
-    Ok(result)
+    /// Who's watching an issue.
+    pub async fn get_watchers(&self, issue_key: &str) -> Result<model::Watches> {
+        let request = self.request(&format!("/issue/{}/watchers", issue_key), Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let watches = response.json::<model::Watches>().await?;
+                Ok(watches)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn add_watcher(&self, issue_key: &str, account_id: &str) -> Result<()> {
+        // The body is just the account ID, as a JSON string
+        let request = self
+            .request(&format!("/issue/{}/watchers", issue_key), Method::POST)
+            .json(account_id);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn remove_watcher(&self, issue_key: &str, account_id: &str) -> Result<()> {
+        let request = self
+            .request(&format!("/issue/{}/watchers", issue_key), Method::DELETE)
+            .query(&[("accountId", account_id)]);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Vote for an issue as the current user, or take the vote back.
+    pub async fn vote(&self, issue_key: &str, vote: bool) -> Result<()> {
+        let method = if vote { Method::POST } else { Method::DELETE };
+        let request = self.request(&format!("/issue/{}/votes", issue_key), method);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Every worklog on an issue, oldest first.
+    pub async fn get_worklogs(&self, issue_key: &str) -> Result<Vec<model::Worklog>> {
+        let mut worklogs = Vec::new();
+        loop {
+            let request = self
+                .request(&format!("/issue/{}/worklog", issue_key), Method::GET)
+                .query(&[("startAt", worklogs.len()), ("maxResults", 1000)]);
+            let response = request.send().await?;
+            let mut page = match response.status() {
+                StatusCode::OK => response.json::<model::WorklogPage>().await?,
+                _ => return Err(Error::from_response(response).await),
+            };
+
+            let page_was_empty = page.worklogs.is_empty();
+            worklogs.append(&mut page.worklogs);
+            if page_was_empty || worklogs.len() >= page.total {
+                break;
+            }
+        }
+        Ok(worklogs)
+    }
+
+    /// Log time against an issue. The remaining estimate goes down by the time logged,
+    /// unless `remaining` sets a new one (e.g. `2h`).
+    pub async fn add_worklog(
+        &self,
+        issue_key: &str,
+        worklog: model::NewWorklog,
+        remaining: Option<&str>,
+    ) -> Result<model::Worklog> {
+        let mut request = self
+            .request(&format!("/issue/{}/worklog", issue_key), Method::POST)
+            .json(&worklog);
+        if let Some(remaining) = remaining {
+            request = request.query(&[("adjustEstimate", "new"), ("newEstimate", remaining)]);
+        }
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::CREATED => {
+                let worklog = response.json::<model::Worklog>().await?;
+                Ok(worklog)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// The files attached to an issue.
+    pub async fn get_attachments(&self, issue_key: &str) -> Result<Vec<model::Attachment>> {
+        let request = self
+            .request(&format!("/issue/{}", issue_key), Method::GET)
+            .query(&[("fields", "attachment")]);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let result = response.json::<model::IssueSearchResult>().await?;
+                Ok(result.fields.attachment.unwrap_or_default())
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Upload files to an issue, returning the new attachments.
+    pub async fn add_attachments<P: AsRef<Path>>(
+        &self,
+        issue_key: &str,
+        paths: &[P],
+    ) -> Result<Vec<model::Attachment>> {
+        let mut form = Form::new();
+        for path in paths {
+            let path = path.as_ref();
+            let filename = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let part = Part::bytes(fs::read(path)?).file_name(filename);
+            form = form.part("file", part);
+        }
+
+        // Without this header Jira rejects uploads, to protect against XSRF
+        let request = self
+            .request(&format!("/issue/{}/attachments", issue_key), Method::POST)
+            .header("X-Atlassian-Token", "no-check")
+            .multipart(form);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let attachments = response.json::<Vec<model::Attachment>>().await?;
+                Ok(attachments)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// The contents of an attached file.
+    pub async fn download_attachment(&self, attachment: &model::Attachment) -> Result<Vec<u8>> {
+        let request = self.request_url(&attachment.content, Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.bytes().await?.to_vec()),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn get_issue(&self, issue_key: &str) -> Result<model::IssueSearchResult> {
+        let result = self.get_issue_simple(issue_key).await?;
+
+        // Enrich issue with PRs
+        let pull_requests = self.get_issue_pull_requests(&result).await?;
+        let result = model::IssueSearchResult {
+            pull_requests,
+            ..result
+        };
+
+        // Enrich issue with the latest comments
+        let comments = Some(self.recent_comments(issue_key, RECENT_COMMENTS).await?);
+        let result = model::IssueSearchResult { comments, ..result };
+
+        // Enrich issue with subtasks
+        let subtasks = self.issue_subtasks(issue_key).await?;
+        let subtasks = Some(subtasks);
+        let result = model::IssueSearchResult { subtasks, ..result };
+
+        // Enrich issue with child issues if this issue is an epic
+        let result = if result.fields.issuetype.name == "Epic" {
+            let epic_issues = Some(self.epic_issues(&result).await?);
+            model::IssueSearchResult {
+                epic_issues,
+                ..result
+            }
+        } else {
+            result
+        };
+
+        // Enrich issue with parent epic if this is part of an epic
+        let result = if let Some(epic) = &result.fields.epic {
+            match epic {
+                model::IssueEpic::Key(k) => model::IssueSearchResult {
+                    fields: model::Issue {
+                        epic: Some(model::IssueEpic::Full(Box::new(
+                            self.get_issue_simple(&k).await?,
+                        ))),
+                        ..result.fields
+                    },
+                    ..result
+                },
+                model::IssueEpic::Full(_) => result,
+            }
+        } else {
+            result
+        };
+
+        Ok(result)
+    }
+
+    /// Fetch an issue's fields, without the PRs, subtasks etc. that `get_issue` adds.
+    pub async fn get_issue_simple(&self, issue_key: &str) -> Result<model::IssueSearchResult> {
+        let request = self.request(&format!("/issue/{}", issue_key), Method::GET);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let mut result = response.json::<model::IssueSearchResult>().await?;
+                let fields = self.get_fields().await?;
+                take_epic(&fields, &mut result.fields);
+                Ok(result)
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Every system and custom field. These are cached on disk for a day, per Jira instance.
+    pub async fn get_fields(&self) -> Result<Arc<model::Fields>> {
+        if let Some(fields) = self.fields.lock().unwrap().as_ref() {
+            return Ok(fields.clone());
+        }
+
+        let fields = match cache::Cache::open().get(&self.fields_cache_name(), FIELDS_MAX_AGE) {
+            Some(fields) => Arc::new(fields),
+            None => self.refresh_fields().await?,
+        };
+        *self.fields.lock().unwrap() = Some(fields.clone());
+        Ok(fields)
+    }
+
+    /// Fetch every field, replacing the cached ones.
+    pub async fn refresh_fields(&self) -> Result<Arc<model::Fields>> {
+        let request = self.request("/field", Method::GET);
+        let response = request.send().await?;
+
+        let fields = match response.status() {
+            StatusCode::OK => Arc::new(response.json::<model::Fields>().await?),
+            _ => return Err(Error::from_response(response).await),
+        };
+        cache::Cache::open().put(&self.fields_cache_name(), fields.as_ref());
+        *self.fields.lock().unwrap() = Some(fields.clone());
+        Ok(fields)
+    }
+
+    fn fields_cache_name(&self) -> String {
+        let host = self
+            .base_url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        format!("fields-{}", host)
+    }
 }
 
-async fn get_issue_simple(
-    issue_key: &str,
-    config: &ApiConfig,
-) -> Result<model::IssueSearchResult, Box<dyn Error>> {
-    let request = build_request(&format!("/issue/{}", issue_key), Method::GET, &config);
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::OK => {
-            // println!("{}", response.text().await?);
-            // std::process::exit(0);
-            let result = response.json::<model::IssueSearchResult>().await?;
-            Ok(result)
-        }
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to fetch issue, {}",
-            code,
-            response.text().await?
-        )))),
+/// Move the epic link out of an issue's custom fields, now that we know its ID.
+fn take_epic(fields: &model::Fields, issue: &mut model::Issue) {
+    let epic = fields
+        .epic_link()
+        .and_then(|id| issue.custom_fields.remove(id));
+    if let Some(serde_json::Value::String(key)) = epic {
+        issue.epic = Some(model::IssueEpic::Key(key));
     }
 }
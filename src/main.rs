@@ -2,186 +2,1693 @@ use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use colored::*;
 use dotenv;
 use jira::model;
+use jira::output::{Column, OutputFormat};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::env;
-use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
 static CREATE_ISSUE_TEMPLATE: &'static str = include_str!("../template/create_issue.md");
 
+const ISSUE_TYPES: [&str; 5] = ["Task", "Bug", "Story", "Sub-task", "Epic"];
+
+/// Distinct exit codes, so wrapper scripts can react to failures without parsing output.
+mod exit_code {
+    pub const FAILURE: i32 = 1;
+    pub const USAGE: i32 = 2;
+    pub const NOT_FOUND: i32 = 3;
+    pub const UNAUTHORIZED: i32 = 4;
+    pub const FORBIDDEN: i32 = 5;
+    pub const RATE_LIMITED: i32 = 6;
+    pub const VALIDATION: i32 = 7;
+    pub const UNSUPPORTED_MARKDOWN: i32 = 8;
+    pub const ABORTED: i32 = 9;
+    pub const NETWORK: i32 = 10;
+}
+
+#[derive(Debug)]
+enum CliError {
+    /// The arguments (or editor input) were invalid.
+    Usage {
+        message: String,
+        hint: Option<String>,
+    },
+    /// The user backed out, e.g. by saving an empty editor buffer.
+    Aborted(String),
+    Jira(jira::Error),
+    /// Creating an issue failed, but what was written in `$EDITOR` is kept as a draft.
+    Draft {
+        error: Box<CliError>,
+        id: String,
+    },
+}
+
+type CliResult<T> = Result<T, CliError>;
+
+impl CliError {
+    fn usage(message: &str, hint: Option<&str>) -> CliError {
+        CliError::Usage {
+            message: message.to_owned(),
+            hint: hint.map(String::from),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage { .. } => exit_code::USAGE,
+            CliError::Aborted(_) => exit_code::ABORTED,
+            CliError::Draft { error, .. } => error.exit_code(),
+            CliError::Jira(e) => match e {
+                jira::Error::NotFound { .. } => exit_code::NOT_FOUND,
+                jira::Error::Unauthorized { .. } => exit_code::UNAUTHORIZED,
+                jira::Error::Forbidden { .. } => exit_code::FORBIDDEN,
+                jira::Error::RateLimited { .. } => exit_code::RATE_LIMITED,
+                jira::Error::Validation { .. } => exit_code::VALIDATION,
+                jira::Error::UnsupportedMarkdown { .. } => exit_code::UNSUPPORTED_MARKDOWN,
+                jira::Error::UnknownTransition { .. } => exit_code::USAGE,
+                jira::Error::UnknownLinkType { .. } => exit_code::USAGE,
+                jira::Error::UnknownField { .. } => exit_code::USAGE,
+                jira::Error::InvalidFieldValue { .. } => exit_code::USAGE,
+                jira::Error::InvalidDraftId { .. } => exit_code::USAGE,
+                jira::Error::FrontMatter { .. } => exit_code::USAGE,
+                jira::Error::Transport(_) => exit_code::NETWORK,
+                _ => exit_code::FAILURE,
+            },
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            CliError::Usage { hint, .. } => hint.clone(),
+            CliError::Aborted(_) => None,
+            CliError::Draft { error, id } => {
+                let resume = format!(
+                    "Your issue was saved as draft {}, run `jira create --resume` to try again.",
+                    id
+                );
+                Some(match error.hint() {
+                    Some(hint) => format!("{}\n{}", hint, resume),
+                    None => resume,
+                })
+            }
+            CliError::Jira(e) => match e {
+                jira::Error::NotFound { .. } => Some(
+                    "Check the issue key, and the project you're scoped to (--project).".to_owned(),
+                ),
+                jira::Error::Unauthorized { .. } => Some(
+                    "Check `JIRA_EMAIL`/`JIRA_TOKEN`; API tokens can be regenerated at \
+                     https://id.atlassian.com/manage-profile/security/api-tokens"
+                        .to_owned(),
+                ),
+                jira::Error::Forbidden { .. } => {
+                    Some("Your Jira user doesn't have permission to do this.".to_owned())
+                }
+                jira::Error::RateLimited { .. } => {
+                    Some("Jira is throttling requests, wait a little and try again.".to_owned())
+                }
+                jira::Error::UnsupportedMarkdown { .. } => Some(
+                    "Remove the unsupported Markdown from the description and try again."
+                        .to_owned(),
+                ),
+                jira::Error::FrontMatter { .. } => Some(
+                    "Front matter is YAML, e.g. `labels: [ios, crash]`; fields you leave out aren't changed."
+                        .to_owned(),
+                ),
+                jira::Error::UnknownField { .. } => Some(
+                    "Run `jira fields` to list the fields on your Jira instance.".to_owned(),
+                ),
+                jira::Error::InvalidDraftId { .. } => {
+                    Some("Run `jira drafts` to list them.".to_owned())
+                }
+                jira::Error::Editor { .. } => {
+                    Some("Set `$EDITOR` to an editor on your `$PATH`.".to_owned())
+                }
+                jira::Error::Transport(_) => Some(
+                    "Check your network connection, and the subdomain/--base-url you're using."
+                        .to_owned(),
+                ),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage { message, .. } => write!(f, "{}", message),
+            CliError::Aborted(message) => write!(f, "Aborting: {}", message),
+            CliError::Jira(e) => write!(f, "{}", e),
+            CliError::Draft { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<jira::Error> for CliError {
+    fn from(e: jira::Error) -> Self {
+        CliError::Jira(e)
+    }
+}
+
+/// How to order an issue list: sorted by `sort` (or as Jira returned it, if that's empty),
+/// then split into sections by `group_by`.
+#[derive(Debug, Default)]
+struct IssueOrder {
+    sort: Vec<jira::sort::SortKey>,
+    group_by: Option<jira::sort::GroupBy>,
+}
+
+impl IssueOrder {
+    /// The order from `--sort` and `--group-by`, sorting by `default` without `--sort`.
+    fn from_args(args: &ArgMatches<'_>, default: Vec<jira::sort::SortKey>) -> CliResult<Self> {
+        let sort = match args.value_of("sort") {
+            Some(spec) => jira::sort::parse_sort(spec).map_err(|e| {
+                CliError::usage(
+                    &e,
+                    Some("Sort by fields, with a - for descending order, e.g. --sort priority,-updated"),
+                )
+            })?,
+            None => default,
+        };
+        let group_by = match args.value_of("group_by") {
+            Some(group_by) => Some(
+                group_by
+                    .parse()
+                    .map_err(|e: String| CliError::usage(&e, None))?,
+            ),
+            None => None,
+        };
+        Ok(IssueOrder { sort, group_by })
+    }
+}
+
+/// Where results go: tables for people, or `--output` for scripts.
+struct Output {
+    format: Option<OutputFormat>,
+    /// The columns of issue lists by name, from `--columns` or the config file; empty uses
+    /// the defaults. Only commands that list issues resolve them, with `issue_columns`.
+    columns: Vec<String>,
+}
+
+impl Output {
+    /// The columns to list issues with.
+    async fn issue_columns(&self, client: &jira::JiraClient) -> CliResult<Vec<Column>> {
+        resolve_columns(&self.columns, client).await
+    }
+
+    fn issues(
+        &self,
+        issues: Vec<model::IssueSearchResult>,
+        columns: &[Column],
+        order: &IssueOrder,
+    ) -> CliResult<()> {
+        let issues = jira::sort::arrange(issues, &order.sort);
+        let groups = match order.group_by {
+            Some(group_by) => jira::sort::group_issues(issues, group_by),
+            None => vec![(String::new(), issues)],
+        };
+
+        match self.format {
+            Some(format) => {
+                // Groups run on one after another, in their order
+                let issues = groups
+                    .into_iter()
+                    .flat_map(|(_, issues)| issues)
+                    .collect::<Vec<_>>();
+                let columns = if columns.is_empty() {
+                    jira::output::DEFAULT_COLUMNS
+                        .iter()
+                        .map(|c| Column::Builtin(c))
+                        .collect::<Vec<_>>()
+                } else {
+                    columns.to_vec()
+                };
+                jira::output::write_issues(io::stdout(), &issues, format, &columns)?
+            }
+            None => {
+                let table_config = jira::format::IssuesTableConfig {
+                    columns: columns.to_vec(),
+                    width: Some(jira::format::terminal_width()),
+                };
+                for (i, (heading, issues)) in groups.into_iter().enumerate() {
+                    if order.group_by.is_some() {
+                        if i > 0 {
+                            println!();
+                        }
+                        println!("{} ({})", heading.bold(), issues.len());
+                    }
+                    jira::format::issues_table(issues, &table_config).printstd();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A single issue. `columns` are only used for CSV and TSV.
+    fn issue(&self, issue: model::IssueSearchResult, columns: &[Column]) -> CliResult<()> {
+        match self.format {
+            Some(OutputFormat::Json) | Some(OutputFormat::Yaml) => {
+                jira::output::write_value(io::stdout(), &issue, self.format.unwrap())?
+            }
+            Some(_) => return self.issues(vec![issue], columns, &IssueOrder::default()),
+            None => jira::format::issue_table(issue),
+        }
+        Ok(())
+    }
+
+    /// Print `value` in the requested format, or `text` if we're printing for people.
+    fn value<T: Serialize>(&self, value: &T, text: &str) -> CliResult<()> {
+        match self.format {
+            Some(format) => jira::output::write_value(io::stdout(), value, format)?,
+            None => println!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+/// Fields that searches need to fetch for `columns`, beyond the usual ones.
+fn column_fields(columns: &[Column]) -> Vec<String> {
+    columns
+        .iter()
+        .filter_map(|c| c.field_id().map(String::from))
+        .collect()
+}
+
+/// Resolve column names, only fetching the fields when some of them aren't built in.
+async fn resolve_columns(names: &[String], client: &jira::JiraClient) -> CliResult<Vec<Column>> {
+    if names.iter().all(|name| Column::builtin(name).is_some()) {
+        return Ok(names
+            .iter()
+            .filter_map(|name| Column::builtin(name))
+            .collect());
+    }
+
+    let fields = client.get_fields().await?;
+    let mut columns = Vec::new();
+    for name in names {
+        columns.push(Column::resolve(name, &fields)?);
+    }
+    Ok(columns)
+}
+
+/// Parse an issue key argument, accepting keys with or without the project prefix.
+fn issue_key_arg(
+    args: &ArgMatches<'_>,
+    name: &str,
+    client: &jira::JiraClient,
+) -> CliResult<String> {
+    issue_key(args.value_of(name).unwrap(), client)
+}
+
+fn issue_key(issue_lossy: &str, client: &jira::JiraClient) -> CliResult<String> {
+    jira::util::issue_lossy_to_issue_key(issue_lossy, client.config()).ok_or_else(|| {
+        CliError::Usage {
+            message: format!("Invalid issue key: '{}'", issue_lossy),
+            hint: Some(format!(
+                "Use a full key like {0}-123, or just the number (123) for issues in {0}.",
+                client.config().project
+            )),
+        }
+    })
+}
+
+#[derive(Serialize)]
+struct CreatedIssue {
+    id: String,
+    key: String,
+    url: String,
+}
+
 // Why is `<'_>` required?
 async fn subcommand_create(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let (title, description) = match (args.value_of("title"), args.value_of("description")) {
-        (Some(t), Some(d)) => (
-            t.to_owned(),
-            Some(jira::convert::markdown_to_adf(&d.to_owned())),
-        ),
-        (Some(t), None) => (t.to_owned(), None),
-        (_, _) => {
-            if let Some((title, description)) =
-                jira::format::text_from_editor(CREATE_ISSUE_TEMPLATE).await?
-            {
-                (title, Some(description))
-            } else {
-                panic!("Aborting: issue title wasn't provided.");
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_type = args.value_of("issuetype").unwrap();
+    let labels = args
+        .values_of("labels")
+        .map(|l| l.map(String::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+    // Fall back to the components configured for this project
+    let components: Vec<String> = match args.values_of("components") {
+        Some(c) => c.map(String::from).collect(),
+        None => client.config().profile.components.clone(),
+    };
+
+    let drafts = jira::drafts::Drafts::open();
+    // `--resume` reopens a draft that Jira rejected, instead of a new template
+    let resume = if args.is_present("resume") {
+        let draft = match args.value_of("resume") {
+            Some(id) => drafts.get(id)?,
+            None => drafts.latest()?,
+        };
+        let draft = draft.ok_or_else(|| {
+            let message = match args.value_of("resume") {
+                Some(id) => format!("There's no draft '{}'", id),
+                None => "There are no drafts to resume".to_owned(),
+            };
+            CliError::usage(&message, Some("Run `jira drafts` to list them."))
+        })?;
+        Some(draft)
+    } else {
+        None
+    };
+    let mut draft_id = resume.as_ref().map(|draft| draft.id.clone());
+
+    let result: CliResult<()> = async {
+        let (title, description, front_matter) =
+            match (args.value_of("title"), args.value_of("description")) {
+                (Some(t), d) => (t.to_owned(), d.unwrap_or_default().to_owned(), None),
+                (None, d) => {
+                    let template = match &resume {
+                        Some(draft) => drafts.read(draft)?,
+                        None => CREATE_ISSUE_TEMPLATE
+                            .replace("{type}", issue_type)
+                            .replace("{labels}", &jira::format::yaml_list(&labels))
+                            .replace("{components}", &jira::format::yaml_list(&components))
+                            .replace("{epic}", &front_matter_value(args.value_of("epic")))
+                            .replace("{parent}", &front_matter_value(args.value_of("parent")))
+                            .replace("{assignee}", "")
+                            .replace("{priority}", "")
+                            .replace("{title}", "")
+                            .replace("{description}", d.unwrap_or_default()),
+                    };
+                    let edited = jira::format::edit_in_editor(&template)?;
+                    // Keep what was written until Jira accepts it
+                    if edited != template || draft_id.is_some() {
+                        draft_id = Some(drafts.save(draft_id.as_deref(), &edited)?);
+                    }
+                    let edited = jira::format::EditorIssue::parse(&edited)?;
+                    (edited.title, edited.description, Some(edited.front_matter))
+                }
+            };
+
+        if title.is_empty() {
+            return Err(CliError::Aborted("issue title wasn't provided.".to_owned()));
+        }
+
+        // The front matter starts out with the values of the flags, so it wins
+        let front_matter = front_matter.unwrap_or_default();
+        let issue_type = front_matter
+            .issuetype
+            .unwrap_or_else(|| issue_type.to_owned());
+        if !ISSUE_TYPES.contains(&issue_type.as_str()) {
+            return Err(CliError::usage(
+                &format!("Unknown issue type '{}'", issue_type),
+                Some(&format!("Use one of: {}.", ISSUE_TYPES.join(", "))),
+            ));
+        }
+        let labels = front_matter.labels.unwrap_or(labels);
+        let components = front_matter.components.unwrap_or(components);
+        let epic = match front_matter.epic {
+            Some(epic) => epic,
+            None => args.value_of("epic").map(String::from),
+        };
+        let parent = match front_matter.parent {
+            Some(parent) => parent,
+            None => args.value_of("parent").map(String::from),
+        };
+
+        let epic = match epic {
+            Some(epic) => Some(issue_key(&epic, client)?),
+            None => None,
+        };
+        if epic.is_some() && !["Task", "Bug", "Story"].contains(&issue_type.as_str()) {
+            return Err(CliError::usage(
+                &format!("Can't create a {} under an epic!", issue_type),
+                Some("Only tasks, bugs and stories can belong to an epic."),
+            ));
+        }
+        let parent = match parent {
+            Some(parent) => Some(issue_key(&parent, client)?),
+            None if issue_type == "Sub-task" => {
+                return Err(CliError::usage(
+                    "A sub-task needs a parent",
+                    Some("Pass --parent, or set `parent` in the front matter."),
+                ))
+            }
+            None => None,
+        };
+
+        // Resolve the assignee up front, so a typo doesn't fail after the issue is created
+        let assignee = match front_matter.assignee.flatten() {
+            Some(assignee) => Some(model::IssueAssignee {
+                account_id: Some(
+                    resolve_user(client, &assignee, UserSearch::Assignable(None))
+                        .await?
+                        .account_id,
+                ),
+                ..Default::default()
+            }),
+            None => None,
+        };
+
+        // Fields from the flags, overridden by any in the front matter
+        let mut custom_fields = field_args(args, client)
+            .await?
+            .into_iter()
+            .map(|(field, value)| (field.id, value))
+            .collect::<HashMap<_, _>>();
+        if let Some(front_matter_fields) = front_matter.fields {
+            let fields = client.get_fields().await?;
+            for (name, value) in front_matter_fields {
+                let field = fields.get(&name)?;
+                let value = match value {
+                    serde_json::Value::String(value) => field.value_from_str(&value)?,
+                    value => value,
+                };
+                custom_fields.insert(field.id.to_owned(), value);
             }
         }
+
+        let issue = model::Issue {
+            summary: title,
+            description: if description.is_empty() {
+                None
+            } else {
+                Some(jira::convert::markdown_to_adf(&description)?)
+            },
+            labels: if labels.is_empty() {
+                None
+            } else {
+                Some(labels)
+            },
+            issuetype: model::IssueType { name: issue_type },
+            components: if components.is_empty() {
+                None
+            } else {
+                Some(
+                    components
+                        .into_iter()
+                        .map(|name| model::Component { name })
+                        .collect(),
+                )
+            },
+            epic: epic.map(model::IssueEpic::Key),
+            parent: parent.map(|key| model::IssueParent {
+                key,
+                ..Default::default()
+            }),
+            assignee,
+            priority: front_matter
+                .priority
+                .flatten()
+                .map(|name| model::IssuePriority { name }),
+            project: Some(model::Project {
+                key: client.config().project.to_owned(),
+            }),
+            custom_fields,
+            ..model::Issue::default()
+        };
+
+        let created = client.create_issue(issue).await?;
+        let created = CreatedIssue {
+            url: client.browse_url(&created.key),
+            id: created.id,
+            key: created.key,
+        };
+        output.value(&created, &created.url)
+    }
+    .await;
+
+    match (result, draft_id) {
+        (Ok(()), Some(id)) => Ok(drafts.delete(&id)?),
+        (Err(error), Some(id)) => Err(CliError::Draft {
+            error: Box::new(error),
+            id,
+        }),
+        (result, None) => result,
+    }
+}
+
+#[derive(Serialize)]
+struct TransitionedIssue {
+    key: String,
+    transition: String,
+    status: String,
+}
+
+async fn subcommand_transition(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    let transition = args.value_of("transition").unwrap();
+
+    let transition = client.transition_issue(&issue_key, transition).await?;
+    let text = format!("{} -> {}", issue_key, transition.to.name);
+    let transitioned = TransitionedIssue {
+        key: issue_key,
+        transition: transition.name,
+        status: transition.to.name,
     };
+    output.value(&transitioned, &text)
+}
 
-    let issue_type = args.value_of("issuetype").unwrap();
-    let epic = args.value_of("epic").map(|e| {
-        let epic = jira::util::issue_lossy_to_issue_key(e, &config);
-        let epic = epic.expect("Invalid epic key!");
-        String::from(epic)
+/// Where `resolve_user` looks for users.
+enum UserSearch<'a> {
+    /// Users who can be assigned this issue, or (without an issue) issues in the project.
+    Assignable(Option<&'a str>),
+    Everyone,
+}
+
+/// Find the user meant by `query`: `me`, a name, an email address or an account ID.
+async fn resolve_user(
+    client: &jira::JiraClient,
+    query: &str,
+    search: UserSearch<'_>,
+) -> CliResult<model::User> {
+    if query == "me" {
+        return Ok(client.get_myself().await?);
+    }
+
+    let (users, who) = match search {
+        UserSearch::Assignable(issue_key) => (
+            client.find_assignable_users(query, issue_key).await?,
+            "assignable user",
+        ),
+        UserSearch::Everyone => (client.find_users(query).await?, "user"),
+    };
+    let interactive = atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr);
+    match_user(query, users, who, interactive)
+}
+
+/// Pick the user `query` means out of the `users` Jira found for it: an exact name, email
+/// address or account ID, or the only result. Otherwise ask which one, if `interactive`.
+fn match_user(
+    query: &str,
+    mut users: Vec<model::User>,
+    who: &str,
+    interactive: bool,
+) -> CliResult<model::User> {
+    let exact = users.iter().position(|u| {
+        u.display_name.eq_ignore_ascii_case(query)
+            || u.email_address.as_deref() == Some(query)
+            || u.account_id == query
     });
+    let exact_matches = users
+        .iter()
+        .filter(|u| u.display_name.eq_ignore_ascii_case(query))
+        .count();
+
+    match (exact, users.len()) {
+        (Some(i), _) if exact_matches <= 1 => Ok(users.swap_remove(i)),
+        (_, 1) => Ok(users.remove(0)),
+        (_, 0) => Err(CliError::usage(
+            &format!("No {} matches \"{}\"", who, query),
+            Some("Use their name or email address as it appears in Jira, or `me`."),
+        )),
+        _ => choose_user(query, users, interactive),
+    }
+}
+
+/// Ask which of several matching users was meant, or fail if there's no one to ask.
+fn choose_user(
+    query: &str,
+    mut users: Vec<model::User>,
+    interactive: bool,
+) -> CliResult<model::User> {
+    let describe = |u: &model::User| match &u.email_address {
+        Some(email) => format!("{} <{}>", u.display_name, email),
+        None => u.display_name.to_owned(),
+    };
+
+    if !interactive {
+        return Err(CliError::usage(
+            &format!("More than one user matches \"{}\"", query),
+            Some(&format!(
+                "Did you mean one of: {}?",
+                users.iter().map(describe).collect::<Vec<_>>().join(", ")
+            )),
+        ));
+    }
+
+    eprintln!("More than one user matches \"{}\":", query);
+    for (i, user) in users.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, describe(user));
+    }
+    eprint!("Which one? [1-{}] ", users.len());
+    io::stderr().flush().map_err(jira::Error::from)?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(jira::Error::from)?;
+    match answer.trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= users.len() => Ok(users.swap_remove(n - 1)),
+        _ => Err(CliError::Aborted("no user was chosen.".to_owned())),
+    }
+}
+
+// A scalar front matter value, after the field's colon.
+fn front_matter_value(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!(" {}", jira::format::yaml_scalar(Some(value))),
+        None => String::new(),
+    }
+}
+
+/// `losses` are what Markdown can't express in the description, which a change would drop.
+fn edit_issue_template(
+    issue: &model::IssueSearchResult,
+    description: &str,
+    losses: &[String],
+    force: bool,
+) -> String {
+    let fields = &issue.fields;
+    let losses = match (losses.is_empty(), force) {
+        (true, _) => String::new(),
+        (false, true) => format!(
+            "# Changing the description replaces it, dropping its {}.\n",
+            losses.join(", ")
+        ),
+        (false, false) => format!(
+            "# The description has {} that Markdown can't express, so it can only be changed\n\
+             # with --force.\n",
+            losses.join(", ")
+        ),
+    };
+    let labels = fields.labels.clone().unwrap_or_default();
+    let components = fields
+        .components
+        .iter()
+        .flatten()
+        .map(|c| c.name.to_owned())
+        .collect::<Vec<_>>();
+
+    format!(
+        "---
+# Editing {key}. Only the fields you change are updated; an empty assignee unassigns it.
+{losses}labels: {labels}
+components: {components}
+assignee:{assignee}
+priority:{priority}
+---
+{title}
+
+{description}
+",
+        key = issue.key,
+        losses = losses,
+        labels = jira::format::yaml_list(&labels),
+        components = jira::format::yaml_list(&components),
+        assignee = front_matter_value(fields.assignee.as_ref().map(|a| a.display_name.as_str())),
+        priority = front_matter_value(fields.priority.as_ref().map(|p| p.name.as_str())),
+        title = fields.summary,
+        description = description,
+    )
+}
+
+/// Parse `--field "Name=value"` arguments into the fields they name and the JSON Jira
+/// expects for each value.
+async fn field_args(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+) -> CliResult<Vec<(model::Field, serde_json::Value)>> {
+    if !args.is_present("field") {
+        return Ok(Vec::new());
+    }
 
-    if let Some(_) = epic {
-        if !(["Task", "Bug", "Story"].contains(&issue_type)) {
-            panic!("Can't create a {} under an epic!", issue_type);
+    let fields = client.get_fields().await?;
+    let mut values = Vec::new();
+    for arg in args.values_of("field").into_iter().flatten() {
+        let (name, value) = match arg.find('=') {
+            Some(i) => (&arg[..i], &arg[i + 1..]),
+            None => {
+                return Err(CliError::usage(
+                    &format!("invalid --field \"{}\"", arg),
+                    Some("Pass a field name and value, e.g. --field \"Story Points=3\"; an empty value clears the field."),
+                ))
+            }
+        };
+        let field = fields.get(name.trim())?;
+        values.push((field.clone(), field.value_from_str(value)?));
+    }
+    Ok(values)
+}
+
+// Compare lists ignoring order, since Jira doesn't preserve it.
+fn same_items(a: &[String], b: &[String]) -> bool {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    a.sort();
+    b.sort();
+    a == b
+}
+
+#[derive(Serialize)]
+struct EditedIssue {
+    key: String,
+    changed: Vec<String>,
+}
+
+async fn subcommand_edit(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    // Setting fields with flags skips the editor, for scripts
+    if args.is_present("field") {
+        let values = field_args(args, client).await?;
+        let changed = values
+            .iter()
+            .map(|(field, _)| field.name.to_owned())
+            .collect();
+        let update = model::IssueUpdate {
+            custom_fields: values
+                .into_iter()
+                .map(|(field, value)| (field.id, value))
+                .collect(),
+            ..Default::default()
+        };
+        client.update_issue(&issue_key, update).await?;
+        return edited_issue(issue_key, changed, output);
+    }
+
+    let issue = client.get_issue_simple(&issue_key).await?;
+    let fields = &issue.fields;
+
+    let description = fields
+        .description
+        .as_ref()
+        .map(jira::convert::adf_to_markdown)
+        .unwrap_or_default();
+    let losses = fields
+        .description
+        .as_ref()
+        .map(jira::convert::markdown_losses)
+        .unwrap_or_default();
+    let force = args.is_present("force");
+    let edited =
+        jira::format::edit_in_editor(&edit_issue_template(&issue, &description, &losses, force))?;
+    let edited = jira::format::EditorIssue::parse(&edited)?;
+
+    if edited.title.is_empty() {
+        return Err(CliError::Aborted("the issue title was removed.".to_owned()));
+    }
+    let create_only = edited.front_matter.create_only_fields();
+    if !create_only.is_empty() {
+        return Err(CliError::usage(
+            &format!(
+                "{} can only be set when creating an issue",
+                create_only.join(", ")
+            ),
+            Some("Remove them from the front matter."),
+        ));
+    }
+
+    let mut update = model::IssueUpdate::default();
+    if edited.title != fields.summary {
+        update.summary = Some(edited.title);
+    }
+    if edited.description != description.trim() {
+        if !losses.is_empty() && !force {
+            return Err(CliError::usage(
+                &format!(
+                    "changing the description would drop its {}",
+                    losses.join(", ")
+                ),
+                Some("Change it in Jira instead, or run again with --force to replace it anyway."),
+            ));
         }
+        update.description = Some(jira::convert::markdown_to_adf(&edited.description)?);
     }
 
-    let issue = model::Issue {
-        summary: title,
-        description,
-        labels: match args.values_of("labels") {
-            Some(l) => Some(l.map(String::from).collect()),
-            None => None,
+    let front_matter = edited.front_matter;
+    if let Some(labels) = front_matter.labels {
+        if !same_items(&labels, fields.labels.as_deref().unwrap_or_default()) {
+            update.labels = Some(labels);
+        }
+    }
+    if let Some(components) = front_matter.components {
+        let current = fields
+            .components
+            .iter()
+            .flatten()
+            .map(|c| c.name.to_owned())
+            .collect::<Vec<_>>();
+        if !same_items(&components, &current) {
+            update.components = Some(
+                components
+                    .into_iter()
+                    .map(|name| model::Component { name })
+                    .collect(),
+            );
+        }
+    }
+    if let Some(priority) = front_matter.priority {
+        if priority.as_deref() != fields.priority.as_ref().map(|p| p.name.as_str()) {
+            update.priority = Some(priority.map(|name| model::IssuePriority { name }));
+        }
+    }
+
+    let mut changed = update
+        .changed_fields()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    client.update_issue(&issue_key, update).await?;
+
+    if let Some(assignee) = front_matter.assignee {
+        let current = fields.assignee.as_ref().map(|a| a.display_name.as_str());
+        if assignee.as_deref() != current {
+            let account_id = match assignee {
+                Some(assignee) => Some(
+                    resolve_user(client, &assignee, UserSearch::Assignable(Some(&issue_key)))
+                        .await?
+                        .account_id,
+                ),
+                None => None,
+            };
+            client
+                .assign_issue(&issue_key, account_id.as_deref())
+                .await?;
+            changed.push("assignee".to_owned());
+        }
+    }
+
+    edited_issue(issue_key, changed, output)
+}
+
+fn edited_issue(issue_key: String, changed: Vec<String>, output: &Output) -> CliResult<()> {
+    let text = if changed.is_empty() {
+        format!("{}: no changes", issue_key)
+    } else {
+        format!("{}: updated {}", issue_key, changed.join(", "))
+    };
+    output.value(
+        &EditedIssue {
+            key: issue_key,
+            changed,
         },
-        issuetype: model::IssueType {
-            name: String::from(issue_type),
+        &text,
+    )
+}
+
+#[derive(Serialize)]
+struct DeletedComment {
+    key: String,
+    id: String,
+}
+
+async fn subcommand_comment(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    if args.is_present("list") {
+        let comments = client.get_comments(&issue_key).await?;
+        match output.format {
+            Some(format) => jira::output::write_value(io::stdout(), &comments, format)?,
+            None if comments.is_empty() => println!("{}: no comments", issue_key),
+            None => println!(
+                "{}",
+                jira::format::comments_text(&comments, jira::format::terminal_width())
+            ),
+        }
+        return Ok(());
+    }
+
+    if let Some(id) = args.value_of("delete") {
+        client.delete_comment(&issue_key, id).await?;
+        let text = format!("{}: deleted comment {}", issue_key, id);
+        return output.value(
+            &DeletedComment {
+                key: issue_key,
+                id: id.to_owned(),
+            },
+            &text,
+        );
+    }
+
+    let current = match args.value_of("edit") {
+        Some(id) => Some(client.get_comment(&issue_key, id).await?),
+        None => None,
+    };
+    let current_text = current
+        .as_ref()
+        .and_then(|comment| comment.body.as_ref())
+        .map(jira::convert::adf_to_markdown)
+        .unwrap_or_default();
+
+    let text = match args.value_of("message") {
+        Some(message) => message.to_owned(),
+        None => jira::format::edit_in_editor(&current_text)?,
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(CliError::Aborted("the comment is empty.".to_owned()));
+    }
+
+    match current {
+        Some(current) if text == current_text.trim() => {
+            let text = format!("{}: no changes to comment {}", issue_key, current.id);
+            output.value(&current, &text)
+        }
+        Some(current) => {
+            let body = jira::convert::markdown_to_adf(text)?;
+            let comment = client.update_comment(&issue_key, &current.id, body).await?;
+            let text = format!("{}: updated comment {}", issue_key, comment.id);
+            output.value(&comment, &text)
+        }
+        None => {
+            let body = jira::convert::markdown_to_adf(text)?;
+            let comment = client.add_comment(&issue_key, body).await?;
+            output.value(&comment, &client.comment_url(&issue_key, &comment.id))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AssignedIssue {
+    key: String,
+    assignee: Option<model::User>,
+}
+
+async fn subcommand_assign(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    let assignee = match args.value_of("user") {
+        Some(user) => {
+            Some(resolve_user(client, user, UserSearch::Assignable(Some(&issue_key))).await?)
+        }
+        None => None,
+    };
+    client
+        .assign_issue(&issue_key, assignee.as_ref().map(|u| u.account_id.as_str()))
+        .await?;
+
+    let text = match &assignee {
+        Some(user) => format!("{}: assigned to {}", issue_key, user.display_name),
+        None => format!("{}: unassigned", issue_key),
+    };
+    output.value(
+        &AssignedIssue {
+            key: issue_key,
+            assignee,
         },
-        components: Some(
-            args.values_of("components")
-                .unwrap()
-                .map(|c| model::Component { name: c.to_owned() })
-                .collect(),
-        ),
-        epic: epic.map(|e| model::IssueEpic::Key(e)),
-        project: Some(model::Project {
-            key: config.project.to_owned(),
-        }),
-        ..model::Issue::default()
+        &text,
+    )
+}
+
+#[derive(Serialize)]
+struct Link {
+    key: String,
+    relation: String,
+    other: String,
+}
+
+async fn subcommand_link(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    let (relation, other) = match (args.value_of("relation"), args.value_of("other")) {
+        (Some(relation), Some(_)) => (relation, issue_key_arg(args, "other", client)?),
+        _ => {
+            // Without a link to add, list the existing ones
+            let links = client
+                .get_issue_simple(&issue_key)
+                .await?
+                .fields
+                .issuelinks
+                .unwrap_or_default();
+            match output.format {
+                Some(format) => jira::output::write_value(io::stdout(), &links, format)?,
+                None if links.is_empty() => println!("{}: no links", issue_key),
+                None => {
+                    jira::format::links_table(&links).printstd();
+                }
+            }
+            return Ok(());
+        }
     };
 
-    let issue = match args.value_of("parent") {
-        Some(parent) => model::Issue {
-            parent: Some(model::IssueParent {
-                key: jira::util::issue_lossy_to_issue_key(parent, config)
-                    .expect("Invalid parent key!")
-                    .to_owned(),
-                ..Default::default()
-            }),
-            ..issue
+    let (link_type, direction) = client.link_issues(&issue_key, relation, &other).await?;
+    let relation = match direction {
+        model::LinkDirection::Outward => link_type.outward,
+        model::LinkDirection::Inward => link_type.inward,
+    };
+    let text = format!("{} {} {}", issue_key, relation, other);
+    output.value(
+        &Link {
+            key: issue_key,
+            relation,
+            other,
         },
-        None => issue,
+        &text,
+    )
+}
+
+async fn subcommand_unlink(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+    let other = issue_key_arg(args, "other", client)?;
+    let relation = args.value_of("relation");
+
+    let issue = client.get_issue_simple(&issue_key).await?;
+    let links = issue
+        .fields
+        .issuelinks
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|link| match link.other() {
+            Some((_, linked)) => {
+                linked.key == other
+                    && relation.map_or(true, |relation| {
+                        model::find_link_type(std::slice::from_ref(&link.link_type), relation)
+                            .is_some()
+                    })
+            }
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    if links.is_empty() {
+        return Err(CliError::usage(
+            &format!("{} isn't linked to {}", issue_key, other),
+            Some(&format!("See its links with `jira link {}`.", issue_key)),
+        ));
+    }
+
+    let mut removed = Vec::new();
+    for link in &links {
+        client.delete_link(&link.id).await?;
+        if let Some((relation, _)) = link.other() {
+            removed.push(Link {
+                key: issue_key.to_owned(),
+                relation: relation.to_owned(),
+                other: other.to_owned(),
+            });
+        }
+    }
+
+    let text = removed
+        .iter()
+        .map(|link| format!("removed: {} {} {}", link.key, link.relation, link.other))
+        .collect::<Vec<_>>()
+        .join("\n");
+    output.value(&removed, &text)
+}
+
+async fn subcommand_attach(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+    let files = args.values_of("files").unwrap().collect::<Vec<_>>();
+    // Check every file up front, rather than failing partway through the upload
+    if let Some(missing) = files.iter().find(|file| !Path::new(file).is_file()) {
+        return Err(CliError::usage(
+            &format!("There's no file at '{}'", missing),
+            None,
+        ));
+    }
+
+    let attachments = client.add_attachments(&issue_key, &files).await?;
+    let text = attachments
+        .iter()
+        .map(|a| {
+            format!(
+                "{}: attached {} ({})",
+                issue_key,
+                a.filename,
+                jira::format::human_size(a.size)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    output.value(&attachments, &text)
+}
+
+#[derive(Serialize)]
+struct SavedAttachment {
+    id: String,
+    filename: String,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Where in `dir` to save an attachment. Only the last component of its filename is used, so
+/// a name from the server can't point outside `dir`, and existing files are never overwritten:
+/// a taken name falls back to `<id>-<name>`, and if that's taken too it's an error.
+fn attachment_path(dir: &Path, attachment: &model::Attachment) -> CliResult<PathBuf> {
+    let name = match Path::new(&attachment.filename).file_name() {
+        Some(name) if name != "." && name != ".." => name.to_owned(),
+        _ => {
+            return Err(CliError::usage(
+                &format!(
+                    "Attachment {} has an unusable filename '{}'",
+                    attachment.id, attachment.filename
+                ),
+                Some("Download it from Jira in your browser instead."),
+            ))
+        }
     };
 
-    jira::create_issue(issue, &config).await?;
+    // symlink_metadata so a dangling symlink counts as taken, and isn't written through
+    let taken = |path: &Path| path.symlink_metadata().is_ok();
+    let path = dir.join(&name);
+    if !taken(&path) {
+        return Ok(path);
+    }
+    let mut fallback = std::ffi::OsString::from(format!("{}-", attachment.id));
+    fallback.push(&name);
+    let fallback = dir.join(fallback);
+    if !taken(&fallback) {
+        return Ok(fallback);
+    }
+    Err(CliError::usage(
+        &format!(
+            "Won't overwrite {} or {}",
+            path.display(),
+            fallback.display()
+        ),
+        Some("Move them out of the way, or save somewhere else with --dir."),
+    ))
+}
 
-    Ok(())
+async fn subcommand_attachments(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+    let attachments = client.get_attachments(&issue_key).await?;
+
+    let ids = match args.values_of("download") {
+        Some(ids) => ids.collect::<Vec<_>>(),
+        None => {
+            match output.format {
+                Some(format) => jira::output::write_value(io::stdout(), &attachments, format)?,
+                None if attachments.is_empty() => println!("{}: no attachments", issue_key),
+                None => {
+                    jira::format::attachments_table(&attachments).printstd();
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let download = if ids.contains(&"all") {
+        attachments.iter().collect::<Vec<_>>()
+    } else {
+        let mut download = Vec::new();
+        for id in ids {
+            match attachments.iter().find(|a| a.id == id) {
+                Some(attachment) => download.push(attachment),
+                None => {
+                    return Err(CliError::usage(
+                        &format!("{} has no attachment '{}'", issue_key, id),
+                        Some(&format!(
+                            "List its attachments with `jira attachments {}`.",
+                            issue_key
+                        )),
+                    ))
+                }
+            }
+        }
+        download
+    };
+
+    let dir = PathBuf::from(args.value_of("dir").unwrap());
+    fs::create_dir_all(&dir).map_err(jira::Error::from)?;
+    let mut saved = Vec::new();
+    for attachment in download {
+        let path = attachment_path(&dir, attachment)?;
+
+        let contents = client.download_attachment(attachment).await?;
+        fs::write(&path, &contents).map_err(jira::Error::from)?;
+        saved.push(SavedAttachment {
+            id: attachment.id.to_owned(),
+            filename: attachment.filename.to_owned(),
+            path,
+            size: contents.len() as u64,
+        });
+    }
+
+    let text = saved
+        .iter()
+        .map(|s| {
+            format!(
+                "saved {} ({})",
+                s.path.display(),
+                jira::format::human_size(s.size)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    output.value(&saved, &text)
 }
 
-async fn subcommand_transition(
+async fn subcommand_log(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let issue_key = args.value_of("issue").unwrap();
-    let issue_key = jira::util::issue_lossy_to_issue_key(issue_key, &config);
-    let issue_key = issue_key.expect("Invalid issue key!");
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+    let duration_hint = Some("Use Jira's format, e.g. `2h30m` or `1d 4h` (a day is 8h).");
 
-    let transition = args.value_of("transition").unwrap();
+    let duration = args.value_of("duration").unwrap();
+    let seconds = match jira::util::parse_duration(duration) {
+        Some(seconds) if seconds >= 60 => seconds,
+        _ => {
+            return Err(CliError::usage(
+                &format!("Invalid time spent: '{}'", duration),
+                duration_hint,
+            ))
+        }
+    };
+    let remaining = args.value_of("remaining");
+    if let Some(remaining) = remaining {
+        if jira::util::parse_duration(remaining).is_none() {
+            return Err(CliError::usage(
+                &format!("Invalid remaining estimate: '{}'", remaining),
+                duration_hint,
+            ));
+        }
+    }
+
+    let now = chrono::Local::now();
+    let started = match args.value_of("started") {
+        Some(started) => jira::util::parse_datetime(started, now).ok_or_else(|| {
+            CliError::usage(
+                &format!("Invalid start time: '{}'", started),
+                Some("Use `2020-06-01 09:30`, or just `09:30` for today."),
+            )
+        })?,
+        // Otherwise the work is assumed to have just finished
+        None => now - chrono::Duration::seconds(seconds as i64),
+    };
+    let comment = match args.value_of("comment") {
+        Some(comment) => Some(jira::convert::markdown_to_adf(comment)?),
+        None => None,
+    };
+
+    let worklog = model::NewWorklog {
+        time_spent_seconds: seconds,
+        started: jira::util::jira_datetime(&started),
+        comment,
+    };
+    let worklog = client.add_worklog(&issue_key, worklog, remaining).await?;
+    let text = format!("{}: logged {}", issue_key, worklog.time_spent);
+    output.value(&worklog, &text)
+}
 
-    jira::update_issue_status(&issue_key, transition.into(), config).await?;
+async fn subcommand_worklog(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
 
+    let worklogs = client.get_worklogs(&issue_key).await?;
+    match output.format {
+        Some(format) => jira::output::write_value(io::stdout(), &worklogs, format)?,
+        None if worklogs.is_empty() => println!("{}: no time logged", issue_key),
+        None => {
+            jira::format::worklogs_table(&worklogs).printstd();
+        }
+    }
     Ok(())
 }
 
+#[derive(Serialize)]
+struct Watching {
+    key: String,
+    watching: bool,
+}
+
+async fn subcommand_watch(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+    watch: bool,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    let me = client.get_myself().await?;
+    if watch {
+        client.add_watcher(&issue_key, &me.account_id).await?;
+    } else {
+        client.remove_watcher(&issue_key, &me.account_id).await?;
+    }
+
+    let text = if watch {
+        format!("{}: watching", issue_key)
+    } else {
+        format!("{}: not watching", issue_key)
+    };
+    output.value(
+        &Watching {
+            key: issue_key,
+            watching: watch,
+        },
+        &text,
+    )
+}
+
+#[derive(Serialize)]
+struct WatcherChanges {
+    key: String,
+    added: Vec<model::User>,
+    removed: Vec<model::User>,
+}
+
+async fn subcommand_watchers(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    if !args.is_present("add") && !args.is_present("remove") {
+        let watches = client.get_watchers(&issue_key).await?;
+        let watchers = watches.watchers.unwrap_or_default();
+        match output.format {
+            Some(format) => jira::output::write_value(io::stdout(), &watchers, format)?,
+            None if watchers.is_empty() => println!("{}: no watchers", issue_key),
+            None => {
+                jira::format::users_table(&watchers).printstd();
+            }
+        }
+        return Ok(());
+    }
+
+    // Resolve everyone first, so a typo doesn't leave the changes half done
+    let mut added = Vec::new();
+    for user in args.values_of("add").into_iter().flatten() {
+        added.push(resolve_user(client, user, UserSearch::Everyone).await?);
+    }
+    let mut removed = Vec::new();
+    for user in args.values_of("remove").into_iter().flatten() {
+        removed.push(resolve_user(client, user, UserSearch::Everyone).await?);
+    }
+
+    let mut lines = Vec::new();
+    for user in &added {
+        client.add_watcher(&issue_key, &user.account_id).await?;
+        lines.push(format!("{}: {} is watching", issue_key, user.display_name));
+    }
+    for user in &removed {
+        client.remove_watcher(&issue_key, &user.account_id).await?;
+        lines.push(format!(
+            "{}: {} stopped watching",
+            issue_key, user.display_name
+        ));
+    }
+
+    output.value(
+        &WatcherChanges {
+            key: issue_key,
+            added,
+            removed,
+        },
+        &lines.join("\n"),
+    )
+}
+
+#[derive(Serialize)]
+struct Voted {
+    key: String,
+    voted: bool,
+}
+
+async fn subcommand_vote(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+    vote: bool,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    client.vote(&issue_key, vote).await?;
+
+    let text = if vote {
+        format!("{}: voted", issue_key)
+    } else {
+        format!("{}: vote removed", issue_key)
+    };
+    output.value(
+        &Voted {
+            key: issue_key,
+            voted: vote,
+        },
+        &text,
+    )
+}
+
 async fn subcommand_take(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let issue_key = args.value_of("issue").unwrap();
-    let issue_key = jira::util::issue_lossy_to_issue_key(issue_key, &config);
-    let issue_key = issue_key.expect("Invalid issue key!");
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
 
-    jira::assign_issue_to_myself(&issue_key, &config).await?;
-    Ok(())
+    let user = client.assign_issue_to_myself(&issue_key).await?;
+    let text = format!("{}: assigned to {}", issue_key, user.display_name);
+    output.value(
+        &AssignedIssue {
+            key: issue_key,
+            assignee: Some(user),
+        },
+        &text,
+    )
 }
 
 async fn subcommand_show(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let issue_key = args.value_of("issue").unwrap();
-    let issue_key = jira::util::issue_lossy_to_issue_key(issue_key, &config);
-    let issue_key = issue_key.expect("Invalid issue key!");
-
-    let result = jira::get_issue(&issue_key, config).await?;
-    jira::format::issue_table(result);
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
+
+    let mut result = client.get_issue(&issue_key).await?;
+
+    if args.is_present("field") {
+        let fields = client.get_fields().await?;
+        let json = serde_json::to_value(&result.fields).map_err(jira::Error::from)?;
+        let mut values = Vec::new();
+        for name in args.values_of("field").into_iter().flatten() {
+            let field = fields.get(name)?;
+            // The epic link is moved out of the custom fields when an issue is fetched
+            let key = if fields.epic_link() == Some(field.id.as_str()) {
+                "epic"
+            } else {
+                &field.id
+            };
+            values.push(model::FieldValue {
+                id: field.id.to_owned(),
+                name: field.name.to_owned(),
+                value: json.get(key).cloned().unwrap_or_default(),
+            });
+        }
+        result.field_values = Some(values);
+    }
+
+    let columns = match output.format {
+        Some(OutputFormat::Csv) | Some(OutputFormat::Tsv) => output.issue_columns(client).await?,
+        _ => Vec::new(),
+    };
+    output.issue(result, &columns)
+}
+
+async fn subcommand_fields(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let fields = if args.is_present("refresh") {
+        client.refresh_fields().await?
+    } else {
+        client.get_fields().await?
+    };
+
+    let search = args.value_of("search").map(str::to_lowercase);
+    let mut fields = fields
+        .0
+        .iter()
+        .filter(|f| f.custom || !args.is_present("custom"))
+        .filter(|f| match &search {
+            Some(search) => f.name.to_lowercase().contains(search) || f.id == *search,
+            None => true,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    fields.sort_by_key(|f| f.name.to_lowercase());
+
+    match output.format {
+        Some(format) => jira::output::write_value(io::stdout(), &fields, format)?,
+        None if fields.is_empty() => println!("No matching fields"),
+        None => {
+            jira::format::fields_table(&fields).printstd();
+        }
+    }
     Ok(())
 }
 
+#[derive(Serialize)]
+struct OpenedIssue {
+    key: String,
+    url: String,
+}
+
 async fn subcommand_open(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
-    let issue_key = args.value_of("issue").unwrap();
-    let issue_key = jira::util::issue_lossy_to_issue_key(issue_key, &config);
-    let issue_key = issue_key.expect("Invalid issue key!");
-
-    open::that(format!(
-        "https://{}.atlassian.net/browse/{}",
-        config.subdomain, issue_key
-    ))
-    .unwrap();
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let issue_key = issue_key_arg(args, "issue", client)?;
 
-    Ok(())
+    let url = client.browse_url(&issue_key);
+    open::that(&url).map_err(jira::Error::from)?;
+
+    output.value(
+        &OpenedIssue {
+            key: issue_key,
+            url: url.to_owned(),
+        },
+        &url,
+    )
 }
 
 async fn subcommand_list(
     args: &ArgMatches<'_>,
-    config: &jira::ApiConfig,
-) -> Result<(), Box<dyn Error>> {
+    client: &jira::JiraClient,
+    settings: &jira::config::Config,
+    output: &Output,
+) -> CliResult<()> {
+    let name = args.value_of("query").unwrap_or("me");
+    let query = settings.query(name).ok_or_else(|| {
+        let names = settings.queries().keys().cloned().collect::<Vec<_>>();
+        CliError::usage(
+            &format!("no list named \"{}\"", name),
+            Some(&format!(
+                "Available lists: {}. Save your own under [queries] in your config file.",
+                names.join(", ")
+            )),
+        )
+    })?;
+
+    let default_sort = if query.sort {
+        jira::sort::default_sort()
+    } else {
+        Vec::new()
+    };
+    let order = IssueOrder::from_args(args, default_sort)?;
+    let columns = output.issue_columns(client).await?;
+
+    if output.format.is_none() {
+        println!("{}", query.title.as_deref().unwrap_or(name).yellow());
+    }
+    let results = client
+        .saved_query_issues(&query, &column_fields(&columns))
+        .await?;
+    output.issues(results, &columns, &order)
+}
+
+async fn subcommand_search(
+    args: &ArgMatches<'_>,
+    client: &jira::JiraClient,
+    output: &Output,
+) -> CliResult<()> {
+    let jql = args.value_of("jql").unwrap();
+    let limit = match args.value_of("limit") {
+        Some(limit) => Some(limit.parse::<usize>().map_err(|_| {
+            CliError::usage(
+                &format!("invalid --limit \"{}\"", limit),
+                Some("Pass a whole number, e.g. --limit 20"),
+            )
+        })?),
+        None => None,
+    };
+    let order = IssueOrder::from_args(args, Vec::new())?;
+    let columns = output.issue_columns(client).await?;
+    let options = jira::search::SearchOptions {
+        fields: args
+            .values_of("fields")
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .chain(column_fields(&columns))
+            .collect(),
+        limit,
+        order_by: args.value_of("order_by").map(|o| o.to_owned()),
+    };
+
+    let results = client.search(jql, &options).await?;
+    output.issues(results, &columns, &order)
+}
+
+fn subcommand_drafts(args: &ArgMatches<'_>, output: &Output) -> CliResult<()> {
+    let drafts = jira::drafts::Drafts::open();
     match args.subcommand() {
-        ("backlog", Some(_)) => {
-            println!("{}", "Issues in the backlog".yellow());
-            let results = jira::search::backlog_issues(&config).await?;
-            let table = jira::format::issues_table(results, &jira::format::IssuesTableConfig::sorted());
-            table.printstd();
-        }
-        ("epics", Some(_)) => {
-            println!("{}", "Epics".yellow());
-            let results = jira::search::epics(&config).await?;
-            let table = jira::format::issues_table(results, &jira::format::IssuesTableConfig::default());
-            table.printstd();
-        }
-        ("me", Some(_)) => {
-            println!("{}", "Issues assigned to me".green());
-            let results = jira::search::issues_assigned_to_me(&config).await?;
-            let table = jira::format::issues_table(results, &jira::format::IssuesTableConfig::sorted());
-            table.printstd();
-        }
-        ("sprint", Some(_)) => {
-            println!("{}", "Issues in the current sprint".blue());
-            let results = jira::search::sprint_issues(&config).await?;
-            let table = jira::format::issues_table(results, &jira::format::IssuesTableConfig::sorted());
-            table.printstd();
+        ("delete", Some(args)) => {
+            let ids: Vec<String> = if args.is_present("all") {
+                drafts.list()?.into_iter().map(|draft| draft.id).collect()
+            } else {
+                args.values_of("draft").unwrap().map(String::from).collect()
+            };
+            for id in &ids {
+                if drafts.get(id)?.is_none() {
+                    return Err(CliError::usage(
+                        &format!("There's no draft '{}'", id),
+                        Some("Run `jira drafts` to list them."),
+                    ));
+                }
+            }
+            for id in &ids {
+                drafts.delete(id)?;
+            }
+            output.value(&ids, &format!("Deleted {} draft(s)", ids.len()))
         }
         _ => {
-            println!("{}", "Issues assigned to me".green());
-            let results = jira::search::issues_assigned_to_me(&config).await?;
-            let table = jira::format::issues_table(results, &jira::format::IssuesTableConfig::sorted());
-            table.printstd();
+            let drafts = drafts.list()?;
+            match output.format {
+                Some(format) => jira::output::write_value(io::stdout(), &drafts, format)?,
+                None if drafts.is_empty() => println!("No drafts"),
+                None => {
+                    jira::format::drafts_table(&drafts).printstd();
+                }
+            }
+            Ok(())
         }
     }
-
-    Ok(())
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("{} {}", "error:".red().bold(), e);
+        if let Some(hint) = e.hint() {
+            for line in hint.lines() {
+                eprintln!("{} {}", "hint:".yellow(), line);
+            }
+        }
+        process::exit(e.exit_code());
+    }
+}
+
+async fn run() -> CliResult<()> {
     dotenv::from_filename(".jira").ok();
 
     let matches = App::new("CLI Jira Interface")
@@ -189,98 +1696,468 @@ async fn main() -> Result<(), Box<dyn Error>> {
             Arg::with_name("subdomain")
                 .long("subdomain")
                 .short("d")
+                .env("JIRA_SUBDOMAIN")
                 .help("Your atlassian.net subdomain")
-                .default_value("heapinc")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("base_url")
+                .long("base-url")
+                .env("JIRA_BASE_URL")
+                .help("The root URL of your Jira instance, if it isn't on atlassian.net")
                 .takes_value(true),
         )
         .arg(
             Arg::with_name("project")
                 .long("project")
                 .short("p")
+                .env("JIRA_PROJECT")
                 .help("Scope the subsequent command to this Jira project")
-                .default_value("HEAP")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .env("JIRA_OUTPUT")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["table", "json", "yaml", "csv", "tsv"])
+                .default_value("table")
+                .help("How to print results; everything but tables is meant for scripts"),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .global(true)
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .help(
+                    "Columns of issue lists, comma separated: key, type, status, summary, \
+                     assignee, priority, labels, epic, updated, sprint, story_points, any field's \
+                     name and more (see the README) [default: the `columns` in your config file]",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("create")
+                .about("Create Jira issues")
+                .arg(
+                    Arg::with_name("title")
+                        .long("title")
+                        .short("t")
+                        .help("Issue title")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("epic")
+                        .long("epic")
+                        .short("e")
+                        .help("Epic that this task belongs to")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("description")
+                        .long("description")
+                        .short("d")
+                        .takes_value(true)
+                        .help("Issue description"),
+                )
+                .arg(
+                    Arg::with_name("issuetype")
+                        .long("issue-type")
+                        .short("y")
+                        .default_value("Task")
+                        .takes_value(true)
+                        .help("Issue type")
+                        .possible_values(&ISSUE_TYPES),
+                )
+                .arg(
+                    Arg::with_name("labels")
+                        .short("l")
+                        .multiple(true)
+                        .takes_value(true)
+                        .help("Issue labels"),
+                )
+                .arg(
+                    Arg::with_name("components")
+                        .short("c")
+                        .multiple(true)
+                        .takes_value(true)
+                        .help("Issue components [default: the project's configured components]"),
+                )
+                .arg(
+                    Arg::with_name("parent")
+                        .long("parent")
+                        .short("p")
+                        .takes_value(true)
+                        .help("Parent issue (if creating a sub-task)"),
+                )
+                .arg(
+                    Arg::with_name("field")
+                        .long("field")
+                        .value_name("NAME=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Set a field by name, e.g. --field \"Story Points=3\""),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .value_name("DRAFT")
+                        .takes_value(true)
+                        .min_values(0)
+                        .conflicts_with("title")
+                        .help("Reopen a draft that failed to create [default: the latest one]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Display a summary of relevant issues. Default: list issues assigned to me.")
+                .arg(
+                    Arg::with_name("query")
+                        .index(1)
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help(
+                            "The list to show: backlog (b), epics (e), me (m), sprint (s), \
+                             or a query saved under [queries] in your config file",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .value_name("FIELDS")
+                        .help(
+                            "Sort by these fields, comma separated, with a - for descending order, \
+                             e.g. priority,-updated. Fields: key, type, status, priority, \
+                             assignee, summary, created, updated, due, epic",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("group_by")
+                        .long("group-by")
+                        .takes_value(true)
+                        .value_name("FIELD")
+                        .possible_values(&["status", "assignee", "epic", "component"])
+                        .help("Split the issues into sections by this field"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("List the issues matching a JQL query")
+                .arg(
+                    Arg::with_name("jql")
+                        .index(1)
+                        .value_name("JQL")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The query to run, e.g. 'project = ABC AND labels = mobile'"),
+                )
+                .arg(
+                    Arg::with_name("fields")
+                        .long("fields")
+                        .short("f")
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .help("Extra fields to fetch, by name or ID, comma separated"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .short("n")
+                        .takes_value(true)
+                        .value_name("COUNT")
+                        .help("The maximum number of issues to show"),
+                )
+                .arg(
+                    Arg::with_name("order_by")
+                        .long("order-by")
+                        .short("o")
+                        .takes_value(true)
+                        .help("How to order the results, e.g. \"updated DESC\""),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .takes_value(true)
+                        .value_name("FIELDS")
+                        .help(
+                            "Sort by these fields, comma separated, with a - for descending order, \
+                             e.g. priority,-updated. Fields: key, type, status, priority, \
+                             assignee, summary, created, updated, due, epic",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("group_by")
+                        .long("group-by")
+                        .takes_value(true)
+                        .value_name("FIELD")
+                        .possible_values(&["status", "assignee", "epic", "component"])
+                        .help("Split the issues into sections by this field"),
+                ),
+        )
         .subcommand(
-            SubCommand::with_name("create")
-                .about("Create Jira issues")
+            SubCommand::with_name("assign")
+                .about("Assign an issue to someone, or unassign it")
                 .arg(
-                    Arg::with_name("title")
-                        .long("title")
-                        .short("t")
-                        .help("Issue title")
-                        .takes_value(true),
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to assign"),
                 )
                 .arg(
-                    Arg::with_name("epic")
-                        .long("epic")
-                        .short("e")
-                        .help("Epic that this task belongs to")
-                        .takes_value(true),
+                    Arg::with_name("user")
+                        .index(2)
+                        .value_name("USER")
+                        .takes_value(true)
+                        .required_unless("unassign")
+                        .help("Their name, email address or account ID, or `me`"),
                 )
                 .arg(
-                    Arg::with_name("description")
-                        .long("description")
-                        .short("d")
+                    Arg::with_name("unassign")
+                        .long("unassign")
+                        .conflicts_with("user")
+                        .help("Leave the issue unassigned"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("link")
+                .about("Link two issues, e.g. `jira link ABC-1 blocks ABC-2`, or list an issue's links")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
                         .takes_value(true)
-                        .help("Issue description"),
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to link from"),
                 )
                 .arg(
-                    Arg::with_name("issuetype")
-                        .long("issue-type")
-                        .short("y")
-                        .default_value("Task")
+                    Arg::with_name("relation")
+                        .index(2)
+                        .value_name("RELATION")
                         .takes_value(true)
-                        .help("Issue type")
-                        .possible_values(&["Task", "Bug", "Story", "Sub-task", "Epic"]),
+                        .requires("other")
+                        .help("How it relates to the other issue, e.g. \"blocks\", \"is blocked by\", \"relates to\", \"duplicates\""),
                 )
                 .arg(
-                    Arg::with_name("labels")
-                        .short("l")
-                        .multiple(true)
+                    Arg::with_name("other")
+                        .index(3)
+                        .value_name("OTHER_ISSUE_KEY")
                         .takes_value(true)
-                        .help("Issue labels"),
+                        .help("The issue to link to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unlink")
+                .about("Remove the links between two issues")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to unlink"),
                 )
                 .arg(
-                    Arg::with_name("components")
-                        .short("c")
+                    Arg::with_name("other")
+                        .index(2)
+                        .value_name("OTHER_ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue it's linked to"),
+                )
+                .arg(
+                    Arg::with_name("relation")
+                        .long("type")
+                        .value_name("RELATION")
+                        .takes_value(true)
+                        .help("Only remove links of this kind, e.g. \"blocks\" [default: every link between them]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attach")
+                .about("Attach files (logs, screenshots, ...) to an issue")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to attach files to"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .index(2)
+                        .value_name("FILE")
+                        .takes_value(true)
                         .multiple(true)
+                        .required(true)
+                        .help("Files to upload"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attachments")
+                .about("List an issue's attachments, or download them")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
                         .takes_value(true)
                         .required(true)
-                        .default_value("Capture")
-                        .help("Issue components"),
+                        .help("The issue (key, with or without the project prefix) to list attachments for"),
                 )
                 .arg(
-                    Arg::with_name("parent")
-                        .long("parent")
-                        .short("p")
+                    Arg::with_name("download")
+                        .long("download")
+                        .value_name("ID|all")
                         .takes_value(true)
-                        .required_if("issuetype", "Sub-task")
-                        .help("Parent issue (if creating a sub-task)"),
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .help("Download these attachments, by ID, or `all` of them"),
+                )
+                .arg(
+                    Arg::with_name("dir")
+                        .long("dir")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .default_value(".")
+                        .help("Where to save downloads"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("list")
-                .about("Display a summary of relevant issues. Default: list issues assigned to me.")
-                .subcommand(
-                    SubCommand::with_name("backlog")
-                        .alias("b")
-                        .help("List all issues in the backlog"),
+            SubCommand::with_name("log")
+                .about("Log time spent on an issue, e.g. `jira log ABC-1 2h30m`")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to log time on"),
                 )
-                .subcommand(
-                    SubCommand::with_name("epics")
-                        .alias("e")
-                        .help("List all epics"),
+                .arg(
+                    Arg::with_name("duration")
+                        .index(2)
+                        .value_name("TIME_SPENT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("How long, e.g. `2h30m` or `1d 4h` (a day is 8h, a week 5d)"),
                 )
-                .subcommand(
-                    SubCommand::with_name("me")
-                        .alias("m")
-                        .help("List all issues assigned to me"),
+                .arg(
+                    Arg::with_name("comment")
+                        .long("comment")
+                        .short("m")
+                        .takes_value(true)
+                        .help("What the time was spent on, in Markdown"),
                 )
-                .subcommand(
-                    SubCommand::with_name("sprint")
-                        .alias("s")
-                        .help("List all issues in the current sprint"),
+                .arg(
+                    Arg::with_name("started")
+                        .long("started")
+                        .value_name("WHEN")
+                        .takes_value(true)
+                        .help("When the work started, e.g. `2020-06-01 09:30` or `09:30` [default: TIME_SPENT ago]"),
+                )
+                .arg(
+                    Arg::with_name("remaining")
+                        .long("remaining")
+                        .value_name("ESTIMATE")
+                        .takes_value(true)
+                        .help("Set the remaining estimate [default: reduce it by TIME_SPENT]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("worklog")
+                .about("List the time logged on an issue")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to list worklogs for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Watch an issue, to be notified when it changes")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to watch"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unwatch")
+                .about("Stop watching an issue")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to stop watching"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watchers")
+                .about("List who's watching an issue, or add and remove watchers")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to list watchers for"),
+                )
+                .arg(
+                    Arg::with_name("add")
+                        .long("add")
+                        .value_name("USER")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Add a watcher, by name, email address or account ID"),
+                )
+                .arg(
+                    Arg::with_name("remove")
+                        .long("remove")
+                        .value_name("USER")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Remove a watcher, by name, email address or account ID"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("vote")
+                .about("Vote for an issue")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to vote for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unvote")
+                .about("Take back your vote for an issue")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to take your vote back from"),
                 ),
         )
         .subcommand(
@@ -295,6 +2172,33 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         .help("The issue (key, with or without the project prefix) to assign to yourself"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .alias("e")
+                .about("Edit an issue's title, description and fields in $EDITOR")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .value_name("ISSUE_KEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The issue (key, with or without the project prefix) to edit"),
+                )
+                .arg(
+                    Arg::with_name("field")
+                        .long("field")
+                        .value_name("NAME=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Set a field by name without opening $EDITOR, e.g. --field \"Story Points=3\""),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Replace the description even if that drops attachments, panels or mentions"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("open")
                 .alias("o")
@@ -319,6 +2223,77 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         .required(true)
                         .value_name("ISSUE_KEY")
                         .help("The issue (key, with or without the project prefix) to show details for"),
+                )
+                .arg(
+                    Arg::with_name("field")
+                        .long("field")
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Also show a field by name, e.g. --field \"Story Points\""),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fields")
+                .about("List the system and custom fields on your Jira instance")
+                .arg(
+                    Arg::with_name("search")
+                        .index(1)
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .help("Only list fields whose name contains this"),
+                )
+                .arg(
+                    Arg::with_name("custom")
+                        .long("custom")
+                        .help("Only list custom fields"),
+                )
+                .arg(
+                    Arg::with_name("refresh")
+                        .long("refresh")
+                        .help("Fetch the fields again instead of using the ones cached for a day"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("comment")
+                .about("Comment on an issue in $EDITOR, or list, edit and delete its comments")
+                .arg(
+                    Arg::with_name("issue")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("ISSUE_KEY")
+                        .help("The issue (key, with or without the project prefix) to comment on"),
+                )
+                .arg(
+                    Arg::with_name("message")
+                        .long("message")
+                        .short("m")
+                        .takes_value(true)
+                        .help("The comment, in Markdown, instead of writing it in $EDITOR"),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .long("list")
+                        .short("l")
+                        .conflicts_with_all(&["message", "edit", "delete"])
+                        .help("List the issue's comments, oldest first"),
+                )
+                .arg(
+                    Arg::with_name("edit")
+                        .long("edit")
+                        .takes_value(true)
+                        .value_name("COMMENT_ID")
+                        .help("Edit one of your comments"),
+                )
+                .arg(
+                    Arg::with_name("delete")
+                        .long("delete")
+                        .takes_value(true)
+                        .value_name("COMMENT_ID")
+                        .conflicts_with_all(&["message", "edit"])
+                        .help("Delete one of your comments"),
                 ),
         )
         .subcommand(
@@ -339,35 +2314,241 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         .short("t")
                         .takes_value(true)
                         .required(true)
-                        .possible_values(&["todo", "in-progress", "review", "closed", "done"])
-                        .help("Status to transition the issue to"),
+                        .help(
+                            "Transition (or status) to move the issue to, e.g. \"in-progress\". \
+                             Matched case-insensitively against the issue's workflow.",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("drafts")
+                .about("List (or delete) issues written with `jira create` that Jira rejected")
+                .subcommand(SubCommand::with_name("list").about("List drafts, newest first"))
+                .subcommand(
+                    SubCommand::with_name("delete")
+                        .alias("rm")
+                        .about("Delete drafts")
+                        .arg(
+                            Arg::with_name("draft")
+                                .index(1)
+                                .value_name("DRAFT")
+                                .multiple(true)
+                                .required_unless("all")
+                                .help("Drafts to delete, by id"),
+                        )
+                        .arg(
+                            Arg::with_name("all")
+                                .long("all")
+                                .conflicts_with("draft")
+                                .help("Delete every draft"),
+                        ),
                 ),
         )
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::VersionlessSubcommands)
         .get_matches();
 
-    let email = env::var("JIRA_EMAIL").expect("A `JIRA_EMAIL` is required");
-    let token = env::var("JIRA_TOKEN").expect("A `JIRA_TOKEN` is required");
-    let subdomain = matches.value_of("subdomain").unwrap();
-    let project = matches.value_of("project").unwrap();
+    let output = Output {
+        format: match matches.value_of("output") {
+            Some("table") | None => None,
+            Some(format) => Some(
+                format
+                    .parse()
+                    .map_err(|e: String| CliError::usage(&e, None))?,
+            ),
+        },
+        columns: Vec::new(),
+    };
+
+    // Drafts are local, so they don't need any Jira settings
+    if let ("drafts", Some(args)) = matches.subcommand() {
+        return subcommand_drafts(args, &output);
+    }
+
+    let email = env::var("JIRA_EMAIL").map_err(|_| {
+        CliError::usage(
+            "A `JIRA_EMAIL` is required",
+            Some("Set it in your environment, or in a `.jira` file in the current directory."),
+        )
+    })?;
+    let token = env::var("JIRA_TOKEN").map_err(|_| {
+        CliError::usage(
+            "A `JIRA_TOKEN` is required",
+            Some("Set it in your environment, or in a `.jira` file in the current directory."),
+        )
+    })?;
+
+    // Flags and environment variables take precedence over the config file
+    let settings = jira::config::Config::load()?;
+    let config_hint = match jira::config::Config::path() {
+        Some(path) => format!("or set it in {}", path.display()),
+        None => "or set it in your config file".to_owned(),
+    };
+
+    let base_url = matches
+        .value_of("base_url")
+        .map(String::from)
+        .or(settings.base_url.clone());
+    let subdomain = matches
+        .value_of("subdomain")
+        .map(String::from)
+        .or(settings.subdomain.clone());
+    let subdomain = match (subdomain, &base_url) {
+        (Some(subdomain), _) => subdomain,
+        (None, Some(_)) => String::new(),
+        (None, None) => {
+            return Err(CliError::usage(
+                "A Jira subdomain (or base URL) is required",
+                Some(&format!(
+                    "Pass --subdomain, set `JIRA_SUBDOMAIN`, {}.",
+                    config_hint
+                )),
+            ))
+        }
+    };
+    let project = matches
+        .value_of("project")
+        .map(String::from)
+        .or(settings.project.clone())
+        .ok_or_else(|| {
+            CliError::usage(
+                "A Jira project is required",
+                Some(&format!(
+                    "Pass --project, set `JIRA_PROJECT`, {}.",
+                    config_hint
+                )),
+            )
+        })?;
+    let profile = settings.profile(&project);
 
     let config = jira::ApiConfig {
         email: email.to_owned(),
         token: token.to_owned(),
-        subdomain: subdomain.to_owned(),
-        project: project.to_owned(),
+        subdomain,
+        project,
+        base_url,
+        profile,
+    };
+    let client = jira::JiraClient::new(config);
+
+    let output = Output {
+        columns: matches
+            .values_of("columns")
+            .map(|c| c.map(String::from).collect::<Vec<_>>())
+            .or_else(|| settings.columns.clone())
+            .unwrap_or_default(),
+        ..output
     };
 
     match matches.subcommand() {
-        ("create", Some(args)) => subcommand_create(&args, &config).await?,
-        ("list", Some(args)) => subcommand_list(&args, &config).await?,
-        ("take", Some(args)) => subcommand_take(&args, &config).await?,
-        ("transition", Some(args)) => subcommand_transition(&args, &config).await?,
-        ("show", Some(args)) => subcommand_show(&args, &config).await?,
-        ("open", Some(args)) => subcommand_open(&args, &config).await?,
-        _ => panic!("Invalid subcommand"),
+        ("create", Some(args)) => subcommand_create(&args, &client, &output).await?,
+        ("list", Some(args)) => subcommand_list(&args, &client, &settings, &output).await?,
+        ("search", Some(args)) => subcommand_search(&args, &client, &output).await?,
+        ("edit", Some(args)) => subcommand_edit(&args, &client, &output).await?,
+        ("comment", Some(args)) => subcommand_comment(&args, &client, &output).await?,
+        ("assign", Some(args)) => subcommand_assign(&args, &client, &output).await?,
+        ("link", Some(args)) => subcommand_link(&args, &client, &output).await?,
+        ("unlink", Some(args)) => subcommand_unlink(&args, &client, &output).await?,
+        ("attach", Some(args)) => subcommand_attach(&args, &client, &output).await?,
+        ("attachments", Some(args)) => subcommand_attachments(&args, &client, &output).await?,
+        ("log", Some(args)) => subcommand_log(&args, &client, &output).await?,
+        ("worklog", Some(args)) => subcommand_worklog(&args, &client, &output).await?,
+        ("watch", Some(args)) => subcommand_watch(&args, &client, &output, true).await?,
+        ("unwatch", Some(args)) => subcommand_watch(&args, &client, &output, false).await?,
+        ("watchers", Some(args)) => subcommand_watchers(&args, &client, &output).await?,
+        ("vote", Some(args)) => subcommand_vote(&args, &client, &output, true).await?,
+        ("unvote", Some(args)) => subcommand_vote(&args, &client, &output, false).await?,
+        ("take", Some(args)) => subcommand_take(&args, &client, &output).await?,
+        ("transition", Some(args)) => subcommand_transition(&args, &client, &output).await?,
+        ("show", Some(args)) => subcommand_show(&args, &client, &output).await?,
+        ("fields", Some(args)) => subcommand_fields(&args, &client, &output).await?,
+        ("open", Some(args)) => subcommand_open(&args, &client, &output).await?,
+        ("drafts", _) => unreachable!("drafts are handled before connecting to Jira"),
+        _ => unreachable!("clap requires a subcommand"),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Vec<model::User> {
+        serde_json::from_str(
+            r#"[{"accountId": "u1", "displayName": "Jane Doe", "emailAddress": "jane@example.com"},
+                {"accountId": "u2", "displayName": "Jane Roe", "emailAddress": "roe@example.com"},
+                {"accountId": "u3", "displayName": "Jane Doe"}]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_match_user_by_email_or_name() {
+        let user = match_user("roe@example.com", users(), "user", false).unwrap();
+        assert_eq!(user.account_id, "u2");
+        let user = match_user("jane roe", users(), "user", false).unwrap();
+        assert_eq!(user.account_id, "u2");
+        let user = match_user("u3", users(), "user", false).unwrap();
+        assert_eq!(user.account_id, "u3");
+
+        // Jira's search is fuzzy, so a single result is taken even if it isn't exact
+        let one = users().into_iter().take(1).collect();
+        assert_eq!(
+            match_user("jan", one, "user", false).unwrap().account_id,
+            "u1"
+        );
+
+        match match_user("nobody", Vec::new(), "assignable user", false) {
+            Err(CliError::Usage { message, .. }) => {
+                assert_eq!(message, "No assignable user matches \"nobody\"")
+            }
+            result => panic!("Unexpected result: {:?}", result.map(|u| u.account_id)),
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_user_without_a_terminal() {
+        // Two users share the name, so it has to ask, and can't
+        for query in &["Jane Doe", "jane"] {
+            match match_user(query, users(), "user", false) {
+                Err(CliError::Usage { message, hint }) => {
+                    assert_eq!(message, format!("More than one user matches \"{}\"", query));
+                    assert_eq!(
+                        hint.unwrap(),
+                        "Did you mean one of: Jane Doe <jane@example.com>, \
+                         Jane Roe <roe@example.com>, Jane Doe?"
+                    );
+                }
+                result => panic!("Unexpected result: {:?}", result.map(|u| u.account_id)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_attachment_path_stays_in_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let attachment = |filename: &str| model::Attachment {
+            id: "10001".to_owned(),
+            filename: filename.to_owned(),
+            author: None,
+            created: "2020-01-01T00:00:00.000+0000".to_owned(),
+            size: 0,
+            mime_type: None,
+            content: String::new(),
+        };
+        let path = |filename: &str| attachment_path(dir.path(), &attachment(filename));
+
+        assert_eq!(path("log.txt").unwrap(), dir.path().join("log.txt"));
+        assert_eq!(path("../../.bashrc").unwrap(), dir.path().join(".bashrc"));
+        assert_eq!(path("/etc/passwd").unwrap(), dir.path().join("passwd"));
+        for filename in &["", ".", "..", "a/..", "/"] {
+            assert!(path(filename).is_err(), "{:?} was allowed", filename);
+        }
+
+        fs::write(dir.path().join("log.txt"), "").unwrap();
+        assert_eq!(path("log.txt").unwrap(), dir.path().join("10001-log.txt"));
+        fs::write(dir.path().join("10001-log.txt"), "").unwrap();
+        assert!(path("log.txt").is_err());
+    }
+}
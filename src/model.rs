@@ -1,6 +1,8 @@
 use super::convert;
-use heck::TitleCase;
+use super::Error;
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Deserialize, Serialize, Debug, Default)]
@@ -18,36 +20,100 @@ pub struct Project {
     pub key: String,
 }
 
-// TODO: This can vary based on Jira installation, so make this more dynamic
-#[derive(Deserialize, Serialize, Debug)]
-#[serde(tag = "name")]
-pub enum IssueStatus {
-    #[serde(rename = "To Do")]
-    ToDoLegacy,
-    #[serde(rename = "TODO")]
-    ToDo,
-    Logged,
-    #[serde(rename = "In Progress")]
-    InProgress,
-    #[serde(rename = "Support Triaged")]
-    SupportTriaged,
-    #[serde(rename = "In Review")]
-    InReview,
-    Closed,
+/// The workflow-independent bucket a status belongs to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusCategoryKey {
+    New,
+    Indeterminate,
     Done,
+    #[serde(other)]
+    Undefined,
 }
 
-impl Default for IssueStatus {
+impl Default for StatusCategoryKey {
     fn default() -> Self {
-        IssueStatus::ToDo
+        StatusCategoryKey::Undefined
     }
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusCategory {
+    pub key: StatusCategoryKey,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub color_name: Option<String>,
+}
+
+/// An issue status. Names vary between workflows, so anything that needs to interpret a
+/// status (colouring, sorting) should use `status_category` instead.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueStatus {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub status_category: StatusCategory,
+}
+
 impl fmt::Display for IssueStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = format!("{:?}", self);
-        let s = s.to_title_case();
-        write!(f, "{}", s)
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IssuePriority {
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IssueResolution {
+    pub name: String,
+}
+
+/// A project version, as used in an issue's fix versions.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Version {
+    pub name: String,
+    #[serde(default)]
+    pub released: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<NaiveDate>,
+}
+
+/// (De)serializes Jira's timestamps, e.g. `2020-06-01T09:30:00.000+0200`, which aren't quite
+/// RFC 3339 because of the offset.
+mod timestamp {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<DateTime<FixedOffset>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_str(&value.format(FORMAT).to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => DateTime::parse_from_str(&value, FORMAT)
+                .or_else(|_| DateTime::parse_from_rfc3339(&value))
+                .map(Some)
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
     }
 }
 
@@ -60,7 +126,59 @@ pub struct IssueParent {
 #[derive(Deserialize, Serialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct IssueAssignee {
+    /// Identifies the user when assigning an issue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub display_name: String,
+    /// Only visible if the user's privacy settings allow it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_address: Option<String>,
+}
+
+/// A kind of link between issues, e.g. `Blocks`, which reads "blocks" one way (`outward`)
+/// and "is blocked by" the other (`inward`).
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IssueLinkType {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub inward: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub outward: String,
+}
+
+/// The issue at the other end of a link, with only its summary, status, type and priority.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct LinkedIssue {
+    pub key: String,
+    pub fields: Option<Box<Issue>>,
+}
+
+/// A link from an issue to another one. Only one of `inward_issue` (for links that read
+/// "<this issue> <inward> <inward_issue>") and `outward_issue` is set.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueLink {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub link_type: IssueLinkType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inward_issue: Option<LinkedIssue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outward_issue: Option<LinkedIssue>,
+}
+
+impl IssueLink {
+    /// The linked issue, and how this issue relates to it, e.g. "blocks".
+    pub fn other(&self) -> Option<(&str, &LinkedIssue)> {
+        match (&self.outward_issue, &self.inward_issue) {
+            (Some(issue), _) => Some((&self.link_type.outward, issue)),
+            (None, Some(issue)) => Some((&self.link_type.inward, issue)),
+            (None, None) => None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -88,9 +206,40 @@ pub struct Issue {
     pub parent: Option<IssueParent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub assignee: Option<IssueAssignee>,
-    #[serde(rename = "customfield_10008")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<IssuePriority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<IssueAssignee>,
+    #[serde(default, with = "timestamp", skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<FixedOffset>>,
+    #[serde(default, with = "timestamp", skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<FixedOffset>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duedate: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<IssueResolution>,
+    #[serde(rename = "fixVersions")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix_versions: Option<Vec<Version>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<convert::Node>,
+    /// The classic epic link, whose field ID differs between instances. `JiraClient` moves
+    /// it in and out of `custom_fields` using `Fields::epic_link`.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     pub epic: Option<IssueEpic>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuelinks: Option<Vec<IssueLink>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Vec<Attachment>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timetracking: Option<TimeTracking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watches: Option<Watches>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub votes: Option<Votes>,
+    /// Every other field, keyed by ID (e.g. `customfield_10016`).
+    #[serde(flatten)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -99,38 +248,596 @@ pub struct IssueSearchResult {
     pub key: String,
     pub fields: Issue,
 
-    // Enrichment
-    #[serde(skip)]
+    // Enrichment; never part of Jira's response, but included in our JSON/YAML output
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "pullRequests")]
     pub pull_requests: Option<Vec<super::graphql::PullRequest>>,
-    #[serde(skip)]
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "epicIssues")]
     pub epic_issues: Option<Vec<IssueSearchResult>>,
-    #[serde(skip)]
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     pub subtasks: Option<Vec<IssueSearchResult>>,
+    /// The most recent comments, oldest first.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<CommentPage>,
+    /// Custom fields picked by name, in the order they were asked for.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fieldValues")]
+    pub field_values: Option<Vec<FieldValue>>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct User {
-    #[serde(rename = "accountId")]
     pub account_id: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub email_address: Option<String>,
+}
+
+/// How many people watch an issue, and whether the current user is one of them.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Watches {
+    pub watch_count: usize,
+    pub is_watching: bool,
+    /// Only included by `GET /issue/{key}/watchers`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchers: Option<Vec<User>>,
+}
+
+/// How many people voted for an issue, and whether the current user is one of them.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Votes {
+    pub votes: usize,
+    pub has_voted: bool,
+}
+
+/// An issue's estimates and logged time, as durations like `1d 4h`.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeTracking {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_estimate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_estimate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_spent: Option<String>,
+}
+
+/// Time logged against an issue.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Worklog {
+    pub id: String,
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default)]
+    pub comment: Option<convert::Node>,
+    pub started: String,
+    pub time_spent: String,
+    pub time_spent_seconds: u64,
+}
+
+/// A page of worklogs from `GET /issue/{key}/worklog`.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorklogPage {
+    pub start_at: usize,
+    pub max_results: usize,
+    pub total: usize,
+    pub worklogs: Vec<Worklog>,
+}
+
+/// Time to log, for `POST /issue/{key}/worklog`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWorklog {
+    pub time_spent_seconds: u64,
+    /// When the work started, e.g. `2020-06-01T09:30:00.000+0100`.
+    pub started: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<convert::Node>,
+}
+
+/// A file attached to an issue.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub filename: String,
+    #[serde(default)]
+    pub author: Option<User>,
+    pub created: String,
+    /// In bytes.
+    pub size: u64,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// The URL to download the file from, with the same credentials as the API.
+    pub content: String,
+}
+
+/// A comment on an issue. Dates are as Jira sends them, e.g. `2020-06-01T09:30:00.000+0000`.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: String,
+    /// Missing for comments left by anonymous users or apps.
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default)]
+    pub body: Option<convert::Node>,
+    pub created: String,
+    #[serde(default)]
+    pub updated: String,
+}
+
+/// A page of comments from `GET /issue/{key}/comment`.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentPage {
+    pub start_at: usize,
+    pub max_results: usize,
+    /// The number of comments on the issue, not just on this page.
+    pub total: usize,
+    pub comments: Vec<Comment>,
+}
+
+/// The fields to change in `PUT /issue/{key}`; fields left as `None` aren't touched.
+#[derive(Serialize, Debug, Default)]
+pub struct IssueUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<convert::Node>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<Component>>,
+    /// `Some(None)` clears the priority.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Option<IssuePriority>>,
+    /// Any other fields, by ID. A null value clears the field.
+    #[serde(flatten)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
+}
+
+impl IssueUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.changed_fields().is_empty()
+    }
+
+    /// The names of the fields this update changes, or IDs for custom fields.
+    pub fn changed_fields(&self) -> Vec<&str> {
+        let mut fields = Vec::new();
+        if self.summary.is_some() {
+            fields.push("summary");
+        }
+        if self.description.is_some() {
+            fields.push("description");
+        }
+        if self.labels.is_some() {
+            fields.push("labels");
+        }
+        if self.components.is_some() {
+            fields.push("components");
+        }
+        if self.priority.is_some() {
+            fields.push("priority");
+        }
+        let mut custom_fields = self
+            .custom_fields
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        custom_fields.sort();
+        fields.append(&mut custom_fields);
+        fields
+    }
 }
 
+/// A reference to a transition, as sent when transitioning an issue.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct IssueTransition {
-    pub id: usize,
-}
-
-// TODO: Don't hardcode these IDs
-impl From<&str> for IssueTransition {
-    fn from(s: &str) -> Self {
-        let id = match s {
-            "todo" => 11,
-            "in-progress" => 21,
-            "review" => 51,
-            "closed" => 41,
-            "done" => 31,
-            _ => panic!("Invalid status!"),
+    pub id: String,
+}
+
+/// A transition that's currently available for an issue, from `GET /issue/{key}/transitions`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Transition {
+    pub id: String,
+    pub name: String,
+    pub to: IssueStatus,
+}
+
+// Compare names ignoring case and punctuation, so "in-progress" matches "In Progress".
+fn normalize_name(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Find the transition matching `name`, either by the transition's own name (preferred),
+/// by the name of the status it leads to, or by its ID.
+pub fn find_transition<'a>(transitions: &'a [Transition], name: &str) -> Option<&'a Transition> {
+    let name = normalize_name(name);
+
+    transitions
+        .iter()
+        .find(|t| normalize_name(&t.name) == name)
+        .or_else(|| {
+            transitions
+                .iter()
+                .find(|t| normalize_name(&t.to.name) == name)
+        })
+        .or_else(|| transitions.iter().find(|t| t.id == name))
+}
+
+/// Which way a link reads from the first issue named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkDirection {
+    /// "A blocks B"
+    Outward,
+    /// "A is blocked by B"
+    Inward,
+}
+
+/// Find the link type matching `name`: either its outward or inward description ("blocks",
+/// "is blocked by"), or its name ("Blocks", which reads outward).
+pub fn find_link_type<'a>(
+    link_types: &'a [IssueLinkType],
+    name: &str,
+) -> Option<(&'a IssueLinkType, LinkDirection)> {
+    let name = normalize_name(name);
+    let matches = |s: &str| normalize_name(s) == name;
+
+    link_types
+        .iter()
+        .find(|t| matches(&t.outward))
+        .map(|t| (t, LinkDirection::Outward))
+        .or_else(|| {
+            link_types
+                .iter()
+                .find(|t| matches(&t.inward))
+                .map(|t| (t, LinkDirection::Inward))
+        })
+        .or_else(|| {
+            link_types
+                .iter()
+                .find(|t| matches(&t.name))
+                .map(|t| (t, LinkDirection::Outward))
+        })
+}
+
+/// The type of a field's values, from `GET /field`. `items` is the type of each element
+/// when `field_type` is `array`, and `custom` identifies the plugin behind a custom field.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct FieldSchema {
+    #[serde(rename = "type")]
+    pub field_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom: Option<String>,
+}
+
+/// A system or custom field, e.g. `{"id": "customfield_10016", "name": "Story Points"}`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Field {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub custom: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<FieldSchema>,
+}
+
+impl Field {
+    /// The JSON Jira expects for this field, from a value typed on the command line. Numbers
+    /// are parsed, select lists take the option's name, lists are comma separated, and an
+    /// empty value clears the field.
+    pub fn value_from_str(&self, value: &str) -> super::Result<serde_json::Value> {
+        use serde_json::{json, Value};
+
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(Value::Null);
+        }
+        let items = || value.split(',').map(str::trim).filter(|v| !v.is_empty());
+        let schema = self.schema.clone().unwrap_or_default();
+
+        Ok(
+            match (schema.field_type.as_str(), schema.items.as_deref()) {
+                ("number", _) => {
+                    let number = value.parse::<f64>().map_err(|_| Error::InvalidFieldValue {
+                        field: self.name.to_owned(),
+                        value: value.to_owned(),
+                        expected: "a number".to_owned(),
+                    })?;
+                    match value.parse::<i64>() {
+                        Ok(integer) => json!(integer),
+                        Err(_) => json!(number),
+                    }
+                }
+                ("option", _) => json!({ "value": value }),
+                ("user", _) => json!({ "accountId": value }),
+                ("array", Some("option")) => items().map(|v| json!({ "value": v })).collect(),
+                ("array", Some("version")) | ("array", Some("component")) => {
+                    items().map(|v| json!({ "name": v })).collect()
+                }
+                ("array", _) => items().map(|v| json!(v)).collect(),
+                ("string", _) | ("date", _) | ("datetime", _) => json!(value),
+                // Anything else has to be given as JSON, but fall back to a plain string
+                _ => serde_json::from_str(value).unwrap_or_else(|_| json!(value)),
+            },
+        )
+    }
+}
+
+/// Every field on a Jira instance, for looking up custom fields by name.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Fields(pub Vec<Field>);
+
+/// The custom field type behind the classic "Epic Link" field.
+const EPIC_LINK_SCHEMA: &str = "com.pyxis.greenhopper.jira:gh-epic-link";
+
+/// The custom field type behind the "Sprint" field.
+const SPRINT_SCHEMA: &str = "com.pyxis.greenhopper.jira:gh-sprint";
+
+impl Fields {
+    /// Find a field by ID, or by name (ignoring case and punctuation).
+    pub fn find(&self, name: &str) -> Option<&Field> {
+        self.0.iter().find(|f| f.id == name).or_else(|| {
+            let name = normalize_name(name);
+            self.0.iter().find(|f| normalize_name(&f.name) == name)
+        })
+    }
+
+    /// Like `find`, but fails with the fields whose names contain `name` as suggestions.
+    pub fn get(&self, name: &str) -> super::Result<&Field> {
+        self.find(name).ok_or_else(|| {
+            let normalized = normalize_name(name);
+            let mut suggestions = self
+                .0
+                .iter()
+                .filter(|f| !normalized.is_empty() && normalize_name(&f.name).contains(&normalized))
+                .map(|f| f.name.to_owned())
+                .collect::<Vec<_>>();
+            suggestions.sort();
+            suggestions.dedup();
+            Error::UnknownField {
+                requested: name.to_owned(),
+                suggestions,
+            }
+        })
+    }
+
+    /// The name of the field with this ID, if there is one.
+    pub fn name(&self, id: &str) -> Option<&str> {
+        self.0.iter().find(|f| f.id == id).map(|f| f.name.as_str())
+    }
+
+    fn find_by_schema(&self, custom: &str) -> Option<&Field> {
+        self.0
+            .iter()
+            .find(|f| f.schema.as_ref().and_then(|s| s.custom.as_deref()) == Some(custom))
+    }
+
+    pub fn sprint(&self) -> Option<&Field> {
+        self.find_by_schema(SPRINT_SCHEMA)
+            .or_else(|| self.0.iter().find(|f| f.custom && f.name == "Sprint"))
+    }
+
+    /// Story points are "Story Points" in company-managed projects, and "Story point
+    /// estimate" in team-managed ones.
+    pub fn story_points(&self) -> Option<&Field> {
+        self.find("Story Points")
+            .or_else(|| self.find("Story point estimate"))
+    }
+
+    /// The ID of the classic "Epic Link" field. Team-managed projects don't have one, and use
+    /// the parent instead.
+    pub fn epic_link(&self) -> Option<&str> {
+        self.find_by_schema(EPIC_LINK_SCHEMA)
+            .or_else(|| self.0.iter().find(|f| f.custom && f.name == "Epic Link"))
+            .map(|f| f.id.as_str())
+    }
+}
+
+/// A custom field's value, alongside its name. Added to issues by `jira show --field`.
+#[derive(Serialize, Debug)]
+pub struct FieldValue {
+    pub id: String,
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+impl From<&Transition> for IssueTransition {
+    fn from(t: &Transition) -> Self {
+        IssueTransition {
+            id: t.id.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transitions() -> Vec<Transition> {
+        vec![
+            ("11", "Back to backlog", "To Do"),
+            ("21", "Start", "In Progress"),
+        ]
+        .into_iter()
+        .map(|(id, name, to)| Transition {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            to: IssueStatus {
+                name: to.to_owned(),
+                ..Default::default()
+            },
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_find_transition_by_name() {
+        let transitions = transitions();
+        assert_eq!(find_transition(&transitions, "start").unwrap().id, "21");
+    }
+
+    #[test]
+    fn test_find_transition_by_target_status() {
+        let transitions = transitions();
+        assert_eq!(
+            find_transition(&transitions, "in-progress").unwrap().id,
+            "21"
+        );
+        assert_eq!(find_transition(&transitions, "TODO").unwrap().id, "11");
+    }
+
+    #[test]
+    fn test_find_transition_missing() {
+        assert!(find_transition(&transitions(), "done").is_none());
+    }
+
+    #[test]
+    fn test_find_link_type() {
+        let link_types = vec![
+            ("Blocks", "is blocked by", "blocks"),
+            ("Duplicate", "is duplicated by", "duplicates"),
+            ("Relates", "relates to", "relates to"),
+        ]
+        .into_iter()
+        .map(|(name, inward, outward)| IssueLinkType {
+            name: name.to_owned(),
+            inward: inward.to_owned(),
+            outward: outward.to_owned(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+        let find = |name| {
+            find_link_type(&link_types, name).map(|(t, direction)| (t.name.as_str(), direction))
         };
+        assert_eq!(find("blocks"), Some(("Blocks", LinkDirection::Outward)));
+        assert_eq!(
+            find("is-blocked-by"),
+            Some(("Blocks", LinkDirection::Inward))
+        );
+        assert_eq!(
+            find("duplicate"),
+            Some(("Duplicate", LinkDirection::Outward))
+        );
+        assert_eq!(
+            find("relates to"),
+            Some(("Relates", LinkDirection::Outward))
+        );
+        assert_eq!(find("clones"), None);
+    }
+
+    #[test]
+    fn test_status_from_any_workflow() {
+        let status: IssueStatus = serde_json::from_str(
+            r#"{"id": "10400", "name": "Awaiting QA", "statusCategory": {"key": "indeterminate", "name": "In Progress", "colorName": "yellow"}}"#,
+        )
+        .unwrap();
+        assert_eq!(status.name, "Awaiting QA");
+        assert_eq!(status.status_category.key, StatusCategoryKey::Indeterminate);
+    }
+
+    #[test]
+    fn test_dates() {
+        let issue: Issue = serde_json::from_str(
+            r#"{"summary": "Crash", "issuetype": {"name": "Bug"}, "created": "2020-06-01T09:30:00.000+0200", "updated": null, "duedate": "2020-06-15"}"#,
+        )
+        .unwrap();
+        let created = issue.created.unwrap();
+        assert_eq!(created.to_rfc3339(), "2020-06-01T09:30:00+02:00");
+        assert!(issue.updated.is_none());
+        assert_eq!(issue.duedate, NaiveDate::from_ymd_opt(2020, 6, 15));
+
+        let json = serde_json::to_value(&issue).unwrap();
+        assert_eq!(json["created"], "2020-06-01T09:30:00.000+0200");
+        assert_eq!(json["duedate"], "2020-06-15");
+        assert!(json.get("updated").is_none());
+    }
+
+    #[test]
+    fn test_watches_and_votes() {
+        let issue: Issue = serde_json::from_str(
+            r#"{"summary": "Crash", "issuetype": {"name": "Bug"},
+                "watches": {"self": "https://x/watchers", "watchCount": 2, "isWatching": true},
+                "votes": {"self": "https://x/votes", "votes": 5, "hasVoted": false}}"#,
+        )
+        .unwrap();
+        let watches = issue.watches.unwrap();
+        assert_eq!((watches.watch_count, watches.is_watching), (2, true));
+        assert!(watches.watchers.is_none());
+        let votes = issue.votes.unwrap();
+        assert_eq!((votes.votes, votes.has_voted), (5, false));
+
+        // As returned by GET /issue/{key}/watchers
+        let watches: Watches = serde_json::from_str(
+            r#"{"self": "https://x/watchers", "isWatching": false, "watchCount": 1,
+                "watchers": [{"accountId": "u1", "displayName": "Jane Doe", "active": true}]}"#,
+        )
+        .unwrap();
+        assert!(!watches.is_watching);
+        let watchers = watches.watchers.unwrap();
+        assert_eq!(watchers[0].display_name, "Jane Doe");
+        assert_eq!(watchers[0].email_address, None);
+    }
+
+    #[test]
+    fn test_custom_fields() {
+        let fields: Fields = serde_json::from_str(
+            r#"[{"id": "summary", "name": "Summary", "custom": false, "schema": {"type": "string", "system": "summary"}},
+                {"id": "customfield_10014", "name": "Epic Link", "custom": true, "schema": {"type": "any", "custom": "com.pyxis.greenhopper.jira:gh-epic-link"}},
+                {"id": "customfield_10016", "name": "Story Points", "custom": true, "schema": {"type": "number"}},
+                {"id": "customfield_10020", "name": "Severity", "custom": true, "schema": {"type": "option"}},
+                {"id": "customfield_10030", "name": "Teams", "custom": true, "schema": {"type": "array", "items": "option"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(fields.epic_link(), Some("customfield_10014"));
+        assert_eq!(fields.find("story points").unwrap().id, "customfield_10016");
+        assert_eq!(fields.find("customfield_10020").unwrap().name, "Severity");
+
+        let points = fields.get("Story Points").unwrap();
+        assert_eq!(points.value_from_str("3").unwrap(), serde_json::json!(3));
+        assert_eq!(
+            points.value_from_str("0.5").unwrap(),
+            serde_json::json!(0.5)
+        );
+        assert_eq!(points.value_from_str("").unwrap(), serde_json::Value::Null);
+        assert!(points.value_from_str("lots").is_err());
+        assert_eq!(
+            fields
+                .get("Severity")
+                .unwrap()
+                .value_from_str("Sev 2")
+                .unwrap(),
+            serde_json::json!({"value": "Sev 2"})
+        );
+        assert_eq!(
+            fields
+                .get("Teams")
+                .unwrap()
+                .value_from_str("Web, iOS")
+                .unwrap(),
+            serde_json::json!([{"value": "Web"}, {"value": "iOS"}])
+        );
 
-        IssueTransition { id }
+        match fields.get("Points") {
+            Err(Error::UnknownField { suggestions, .. }) => {
+                assert_eq!(suggestions, vec!["Story Points"])
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
     }
 }
@@ -0,0 +1,494 @@
+//! Machine-readable output, for piping results into `jq`, spreadsheets and the like.
+//!
+//! JSON and YAML contain everything we know about an issue (including the pull requests,
+//! subtasks and epic issues that `show` fetches). CSV and TSV flatten each issue into a
+//! single row made up of the requested columns: any of `COLUMNS`, or custom fields.
+
+use serde::Serialize;
+use std::io;
+use std::str::FromStr;
+
+use super::model::{Fields, IssueEpic, IssueSearchResult};
+use super::util::local_datetime;
+use super::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+/// The columns an issue can be flattened into.
+pub const COLUMNS: [&str; 23] = [
+    "key",
+    "id",
+    "summary",
+    "type",
+    "status",
+    "status_category",
+    "resolution",
+    "priority",
+    "assignee",
+    "assignee_id",
+    "assignee_email",
+    "reporter",
+    "created",
+    "updated",
+    "due",
+    "project",
+    "parent",
+    "epic",
+    "labels",
+    "components",
+    "fix_versions",
+    "subtasks",
+    "pull_requests",
+];
+
+/// The columns of issue lists, as tables or CSV/TSV, when none are picked.
+pub const DEFAULT_COLUMNS: [&str; 5] = ["key", "type", "status", "summary", "assignee"];
+
+/// System fields that have a column of their own, by field ID.
+const SYSTEM_FIELD_COLUMNS: [(&str, &str); 16] = [
+    ("summary", "summary"),
+    ("issuetype", "type"),
+    ("status", "status"),
+    ("resolution", "resolution"),
+    ("priority", "priority"),
+    ("assignee", "assignee"),
+    ("reporter", "reporter"),
+    ("created", "created"),
+    ("updated", "updated"),
+    ("duedate", "due"),
+    ("project", "project"),
+    ("parent", "parent"),
+    ("labels", "labels"),
+    ("components", "components"),
+    ("fixVersions", "fix_versions"),
+    ("subtasks", "subtasks"),
+];
+
+/// A column of an issue list: one of `COLUMNS`, or any other field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Builtin(&'static str),
+    /// A field without a column of its own (usually a custom field), headed by its name.
+    Field {
+        id: String,
+        name: String,
+    },
+}
+
+impl Column {
+    /// The column called `name`, if it's one of `COLUMNS`.
+    pub fn builtin(name: &str) -> Option<Column> {
+        COLUMNS
+            .iter()
+            .find(|c| **c == name)
+            .map(|c| Column::Builtin(c))
+    }
+
+    /// Resolve a column name: one of `COLUMNS`, `sprint`, `story_points`, or any field's
+    /// name or ID.
+    pub fn resolve(name: &str, fields: &Fields) -> Result<Column> {
+        if let Some(column) = Column::builtin(name) {
+            return Ok(column);
+        }
+
+        let field = match name {
+            "sprint" => fields.sprint(),
+            "story_points" => fields.story_points(),
+            _ => None,
+        };
+        let field = match field {
+            Some(field) => field,
+            None => fields.get(name)?,
+        };
+
+        match SYSTEM_FIELD_COLUMNS.iter().find(|(id, _)| *id == field.id) {
+            Some((_, column)) => Ok(Column::Builtin(column)),
+            None if fields.epic_link() == Some(field.id.as_str()) => Ok(Column::Builtin("epic")),
+            None => Ok(Column::Field {
+                id: field.id.to_owned(),
+                name: field.name.to_owned(),
+            }),
+        }
+    }
+
+    /// The column's name, as used in CSV headers.
+    pub fn name(&self) -> &str {
+        match self {
+            Column::Builtin(name) => name,
+            Column::Field { name, .. } => name,
+        }
+    }
+
+    /// The column's heading in a table, e.g. "Fix versions".
+    pub fn title(&self) -> String {
+        match self {
+            Column::Builtin(name) => {
+                let name = name.replace('_', " ");
+                let mut chars = name.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => name,
+                }
+            }
+            Column::Field { name, .. } => name.to_owned(),
+        }
+    }
+
+    /// The field to fetch for this column, if it isn't fetched already.
+    pub fn field_id(&self) -> Option<&str> {
+        match self {
+            Column::Builtin(_) => None,
+            Column::Field { id, .. } => Some(id),
+        }
+    }
+
+    pub fn value(&self, issue: &IssueSearchResult) -> String {
+        match self {
+            Column::Builtin(name) => column_value(issue, name),
+            Column::Field { id, .. } => issue
+                .fields
+                .custom_fields
+                .get(id)
+                .map(field_text)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The value of one of `COLUMNS` for an issue. Lists are joined with commas, and missing
+/// values are empty.
+pub fn column_value(issue: &IssueSearchResult, column: &str) -> String {
+    let fields = &issue.fields;
+
+    match column {
+        "key" => issue.key.to_owned(),
+        "id" => issue.id.to_owned(),
+        "summary" => fields.summary.to_owned(),
+        "type" => fields.issuetype.name.to_owned(),
+        "status" => fields
+            .status
+            .as_ref()
+            .map(|s| s.name.to_owned())
+            .unwrap_or_default(),
+        "status_category" => fields
+            .status
+            .as_ref()
+            .map(|s| s.status_category.name.to_owned())
+            .unwrap_or_default(),
+        "resolution" => fields
+            .resolution
+            .as_ref()
+            .map(|r| r.name.to_owned())
+            .unwrap_or_default(),
+        "priority" => fields
+            .priority
+            .as_ref()
+            .map(|p| p.name.to_owned())
+            .unwrap_or_default(),
+        "assignee" => fields
+            .assignee
+            .as_ref()
+            .map(|a| a.display_name.to_owned())
+            .unwrap_or_default(),
+        "assignee_id" => fields
+            .assignee
+            .as_ref()
+            .and_then(|a| a.account_id.to_owned())
+            .unwrap_or_default(),
+        "assignee_email" => fields
+            .assignee
+            .as_ref()
+            .and_then(|a| a.email_address.to_owned())
+            .unwrap_or_default(),
+        "reporter" => fields
+            .reporter
+            .as_ref()
+            .map(|r| r.display_name.to_owned())
+            .unwrap_or_default(),
+        "created" => fields
+            .created
+            .as_ref()
+            .map(local_datetime)
+            .unwrap_or_default(),
+        "updated" => fields
+            .updated
+            .as_ref()
+            .map(local_datetime)
+            .unwrap_or_default(),
+        "due" => fields
+            .duedate
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        "project" => fields
+            .project
+            .as_ref()
+            .map(|p| p.key.to_owned())
+            .unwrap_or_default(),
+        "parent" => fields
+            .parent
+            .as_ref()
+            .map(|p| p.key.to_owned())
+            .unwrap_or_default(),
+        "epic" => match &fields.epic {
+            Some(IssueEpic::Key(key)) => key.to_owned(),
+            Some(IssueEpic::Full(epic)) => epic.key.to_owned(),
+            None => String::new(),
+        },
+        "labels" => fields
+            .labels
+            .as_ref()
+            .map(|l| l.join(", "))
+            .unwrap_or_default(),
+        "components" => fields
+            .components
+            .as_ref()
+            .map(|c| {
+                c.iter()
+                    .map(|c| c.name.to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        "fix_versions" => fields
+            .fix_versions
+            .as_ref()
+            .map(|v| {
+                v.iter()
+                    .map(|v| v.name.to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        "subtasks" => issue
+            .subtasks
+            .as_ref()
+            .map(|s| {
+                s.iter()
+                    .map(|s| s.key.to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        "pull_requests" => issue
+            .pull_requests
+            .as_ref()
+            .map(|p| {
+                p.iter()
+                    .map(|p| p.url.to_owned())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// A field value as text: option, version and user fields by name, lists joined with commas,
+/// and rich text as Markdown. Null is empty.
+pub fn field_text(value: &serde_json::Value) -> String {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.to_owned(),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+        Value::Array(items) => items.iter().map(field_text).collect::<Vec<_>>().join(", "),
+        Value::Object(object) => {
+            if object.get("type").and_then(Value::as_str) == Some("doc") {
+                if let Ok(node) = serde_json::from_value(value.clone()) {
+                    return super::convert::adf_to_markdown(&node).trim().to_owned();
+                }
+            }
+            ["value", "name", "displayName", "key"]
+                .iter()
+                .find_map(|key| object.get(*key).and_then(Value::as_str))
+                .map(String::from)
+                .unwrap_or_else(|| value.to_string())
+        }
+    }
+}
+
+fn serialization_error(e: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::Serialization(Box::new(e))
+}
+
+fn csv_writer<W: io::Write>(out: W, format: OutputFormat) -> csv::Writer<W> {
+    let delimiter = match format {
+        OutputFormat::Tsv => b'\t',
+        _ => b',',
+    };
+    csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out)
+}
+
+/// Write a list of issues; CSV and TSV get a header row followed by one row per issue.
+pub fn write_issues<W: io::Write>(
+    mut out: W,
+    issues: &[IssueSearchResult],
+    format: OutputFormat,
+    columns: &[Column],
+) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, issues).map_err(serialization_error)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut out, issues).map_err(serialization_error)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut writer = csv_writer(out, format);
+            writer
+                .write_record(columns.iter().map(Column::name))
+                .map_err(serialization_error)?;
+            for issue in issues {
+                writer
+                    .write_record(columns.iter().map(|c| c.value(issue)))
+                    .map_err(serialization_error)?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write any other result. For CSV and TSV, `value` must be a flat struct; it's written
+/// as a header row and a single record.
+pub fn write_value<W: io::Write, T: Serialize>(
+    mut out: W,
+    value: &T,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, value).map_err(serialization_error)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut out, value).map_err(serialization_error)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut writer = csv_writer(out, format);
+            writer.serialize(value).map_err(serialization_error)?;
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue() -> IssueSearchResult {
+        serde_json::from_str(
+            r#"{"id": "10001", "key": "ABC-1", "fields": {
+                "summary": "Crash, on launch", "issuetype": {"name": "Bug"},
+                "labels": ["ios", "p1"], "parent": null
+            }}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_csv_quotes_values() {
+        let mut out = Vec::new();
+        write_issues(
+            &mut out,
+            &[issue()],
+            OutputFormat::Csv,
+            &[
+                Column::Builtin("key"),
+                Column::Builtin("summary"),
+                Column::Builtin("labels"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "key,summary,labels\nABC-1,\"Crash, on launch\",\"ios, p1\"\n"
+        );
+    }
+
+    #[test]
+    fn test_json_includes_enrichment() {
+        let mut issue = issue();
+        issue.subtasks = Some(vec![]);
+
+        let mut out = Vec::new();
+        write_issues(&mut out, &[issue], OutputFormat::Json, &[]).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["subtasks"], serde_json::json!([]));
+        assert!(json[0].get("pullRequests").is_none());
+    }
+
+    #[test]
+    fn test_field_text() {
+        use serde_json::json;
+
+        assert_eq!(field_text(&json!(null)), "");
+        assert_eq!(field_text(&json!(3.5)), "3.5");
+        assert_eq!(
+            field_text(&json!({"self": "...", "value": "Sev 2"})),
+            "Sev 2"
+        );
+        assert_eq!(
+            field_text(&json!([{"name": "2.1"}, {"name": "2.2"}])),
+            "2.1, 2.2"
+        );
+        assert_eq!(field_text(&json!({"displayName": "Jane Doe"})), "Jane Doe");
+    }
+
+    #[test]
+    fn test_resolve_columns() {
+        let fields: Fields = serde_json::from_str(
+            r#"[{"id": "duedate", "name": "Due date", "schema": {"type": "date"}},
+                {"id": "customfield_10016", "name": "Story Points", "custom": true, "schema": {"type": "number"}},
+                {"id": "customfield_10020", "name": "Sprint", "custom": true, "schema": {"type": "array", "items": "json", "custom": "com.pyxis.greenhopper.jira:gh-sprint"}}]"#,
+        )
+        .unwrap();
+        let resolve = |name| Column::resolve(name, &fields).unwrap();
+        assert_eq!(resolve("fix_versions"), Column::Builtin("fix_versions"));
+        assert_eq!(resolve("Due date"), Column::Builtin("due"));
+        assert_eq!(resolve("sprint").title(), "Sprint");
+        assert_eq!(
+            resolve("story_points").field_id(),
+            Some("customfield_10016")
+        );
+        assert_eq!(resolve("fix_versions").title(), "Fix versions");
+        assert!(Column::resolve("points", &fields).is_err());
+
+        let mut issue = issue();
+        issue.fields.custom_fields.insert(
+            "customfield_10020".to_owned(),
+            serde_json::json!([{"id": 1, "name": "Sprint 12", "state": "active"}]),
+        );
+        assert_eq!(resolve("Sprint").value(&issue), "Sprint 12");
+        assert_eq!(resolve("Story Points").value(&issue), "");
+    }
+}
@@ -1,10 +1,10 @@
+use regex::Regex;
 use reqwest::{Method, StatusCode};
 use serde::Deserialize;
 
-use std::error::Error;
-
+use super::config::SavedQuery;
 use super::model;
-use super::{ApiConfig, ApiError};
+use super::{Error, JiraClient, Result};
 
 #[derive(Deserialize, Debug, Default)]
 struct IssueSearchResponse {
@@ -26,97 +26,352 @@ impl Default for IssueSearchPageCounter {
     }
 }
 
-pub async fn epics(config: &ApiConfig) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = "issuetype = Epic AND status not in (Closed, Done) AND component in (Capture,iOS,Android,Mobile) order by updated ASC";
-    search_issues(search_jql, config).await
-}
+/// The fields needed to render an issue list.
+const DEFAULT_FIELDS: [&str; 15] = [
+    "assignee",
+    "reporter",
+    "priority",
+    "resolution",
+    "created",
+    "updated",
+    "duedate",
+    "fixVersions",
+    "labels",
+    "components",
+    "issuetype",
+    "summary",
+    "status",
+    "project",
+    "parent",
+];
 
-pub async fn issue_subtasks(
-    config: &ApiConfig,
-    issue_key: &str,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = format!("parent = {}", issue_key);
-    search_issues(&search_jql, config).await
+#[derive(Debug, Default)]
+pub struct SearchOptions {
+    /// Fields (names or IDs) to fetch in addition to the ones needed to render an issue list.
+    pub fields: Vec<String>,
+    /// Stop after this many issues.
+    pub limit: Option<usize>,
+    /// A JQL `ORDER BY` clause (without the keyword), e.g. `updated DESC`. It replaces any
+    /// `ORDER BY` the query already ends with.
+    pub order_by: Option<String>,
 }
 
-pub async fn epic_issues(
-    config: &ApiConfig,
-    epic: &model::IssueSearchResult,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = &format!("'Epic Link' = {}", epic.key);
-    search_issues(search_jql, config).await
+/// Quote a value for use in JQL.
+fn jql_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
-pub async fn backlog_issues(
-    config: &ApiConfig,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = "sprint is empty AND component in (Capture,iOS,Android,Mobile) AND (status != Closed AND status != Done)";
-    search_issues(search_jql, config).await
-}
+/// `jql` ordered by `order_by`, in place of its own `ORDER BY` clause if it has one.
+fn order_jql(jql: &str, order_by: Option<&str>) -> String {
+    let order_by = match order_by {
+        Some(order_by) => order_by,
+        None => return jql.to_owned(),
+    };
+
+    // The last ORDER BY that isn't inside a quoted string
+    let keyword = Regex::new(r"(?i)\border\s+by\b").unwrap();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut clause_start = None;
+    for (i, c) in jql.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, _) => {
+                if keyword.find_at(jql, i).map(|m| m.start()) == Some(i) {
+                    clause_start = Some(i);
+                }
+            }
+        }
+    }
 
-pub async fn sprint_issues(
-    config: &ApiConfig,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = "sprint in openSprints () AND component in (Capture,iOS,Android,Mobile)";
-    search_issues(search_jql, config).await
+    let jql = match clause_start {
+        Some(start) => jql[..start].trim_end(),
+        None => jql,
+    };
+    format!("{} ORDER BY {}", jql, order_by)
 }
 
-pub async fn issues_assigned_to_me(
-    config: &ApiConfig,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let search_jql = "assignee = currentUser() AND (status != Closed AND status != Done)";
-    search_issues(search_jql, config).await
+/// The `fields` to ask a search for: the ones issue lists need, the epic link, and `extra`
+/// (by name or ID, since Jira only accepts IDs here).
+fn search_fields(all_fields: &model::Fields, extra: &[String]) -> String {
+    let mut fields = DEFAULT_FIELDS
+        .iter()
+        .map(|f| f.to_string())
+        .chain(all_fields.epic_link().map(String::from))
+        .collect::<Vec<_>>();
+    for field in extra {
+        let field = all_fields
+            .find(field)
+            .map(|f| f.id.to_owned())
+            .unwrap_or_else(|| field.to_owned());
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    fields.join(",")
 }
 
-async fn search_issues(
-    search_jql: &str,
-    config: &ApiConfig,
-) -> Result<Vec<model::IssueSearchResult>, Box<dyn Error>> {
-    let mut start_at = 0;
-    let mut results = Vec::new();
-
-    loop {
-        let mut page = search_issues_single_page(search_jql, start_at, config).await?;
-        start_at = start_at + page.issues.len();
-        results.append(&mut page.issues);
-
-        if page.total > start_at {
-            // Do nothing, fetch another page
-            eprintln!("Fetching a page of results starting at index: {}", start_at);
-        } else {
-            break;
+impl JiraClient {
+    /// JQL that scopes the built-in lists to the current project, and to the components
+    /// and filter in its profile (if any).
+    fn scope_jql(&self) -> String {
+        let config = &self.config;
+        let mut clauses = vec![format!("project = {}", jql_quote(&config.project))];
+
+        if !config.profile.components.is_empty() {
+            let components = config
+                .profile
+                .components
+                .iter()
+                .map(|c| jql_quote(c))
+                .collect::<Vec<_>>();
+            clauses.push(format!("component in ({})", components.join(", ")));
         }
+
+        if let Some(filter) = &config.profile.filter {
+            clauses.push(format!("({})", filter));
+        }
+
+        clauses.join(" AND ")
     }
 
-    Ok(results)
-}
+    /// Fill in the placeholders in a saved query's JQL (see `config`).
+    pub fn expand_jql(&self, jql: &str) -> Result<String> {
+        let config = &self.config;
+        let mut jql = jql
+            .replace("{project}", &jql_quote(&config.project))
+            .replace("{me}", "currentUser()");
+
+        if jql.contains("{component}") {
+            if config.profile.components.is_empty() {
+                return Err(Error::Config {
+                    path: None,
+                    message: format!(
+                        "the query uses {{component}}, but project {} has no components configured",
+                        config.project
+                    ),
+                });
+            }
+            let components = config
+                .profile
+                .components
+                .iter()
+                .map(|c| jql_quote(c))
+                .collect::<Vec<_>>();
+            jql = jql.replace("{component}", &components.join(", "));
+        }
+
+        Ok(jql.replace("{scope}", &self.scope_jql()))
+    }
+
+    /// Run a saved query, fetching `fields` (names or IDs) on top of the usual ones.
+    pub async fn saved_query_issues(
+        &self,
+        query: &SavedQuery,
+        fields: &[String],
+    ) -> Result<Vec<model::IssueSearchResult>> {
+        let search_jql = self.expand_jql(&query.jql)?;
+        let options = SearchOptions {
+            fields: fields.to_vec(),
+            ..Default::default()
+        };
+        self.search(&search_jql, &options).await
+    }
+
+    pub async fn issue_subtasks(&self, issue_key: &str) -> Result<Vec<model::IssueSearchResult>> {
+        let search_jql = format!("parent = {}", issue_key);
+        self.search_issues(&search_jql).await
+    }
+
+    pub async fn epic_issues(
+        &self,
+        epic: &model::IssueSearchResult,
+    ) -> Result<Vec<model::IssueSearchResult>> {
+        let search_jql = &format!("'Epic Link' = {}", epic.key);
+        self.search_issues(search_jql).await
+    }
+
+    /// Run an arbitrary JQL query with the default options, fetching every page of results.
+    pub async fn search_issues(&self, search_jql: &str) -> Result<Vec<model::IssueSearchResult>> {
+        self.search(search_jql, &SearchOptions::default()).await
+    }
+
+    /// Run an arbitrary JQL query, fetching pages of results until `options.limit` is hit.
+    pub async fn search(
+        &self,
+        search_jql: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<model::IssueSearchResult>> {
+        let search_jql = order_jql(search_jql, options.order_by.as_deref());
+        let all_fields = self.get_fields().await?;
+        let fields = search_fields(&all_fields, &options.fields);
+
+        let mut start_at = 0;
+        let mut results = Vec::new();
 
-async fn search_issues_single_page(
-    search_jql: &str,
-    start_at: usize,
-    config: &ApiConfig,
-) -> Result<IssueSearchResponse, Box<dyn Error>> {
-    // TODO: reuse client
-    let request = super::build_request("/search", Method::GET, &config).query(&[
-        ("jql", &search_jql[..]),
-        ("startAt", &start_at.to_string()),
-        (
-            "fields",
-            "assignee,labels,components,issuetype,summary,status,project,parent",
-        ),
-    ]);
-
-    let response = request.send().await?;
-
-    match response.status() {
-        StatusCode::OK => {
-            let results = response.json::<IssueSearchResponse>().await?;
-            Ok(results)
+        loop {
+            let max_results = options.limit.map(|limit| limit - results.len());
+            let mut page = self
+                .search_issues_single_page(&search_jql, &fields, start_at, max_results)
+                .await?;
+            start_at = start_at + page.issues.len();
+            let page_was_empty = page.issues.is_empty();
+            results.append(&mut page.issues);
+
+            let limit_reached = options.limit.map_or(false, |limit| results.len() >= limit);
+            if page.total > start_at && !limit_reached && !page_was_empty {
+                // Do nothing, fetch another page
+                eprintln!("Fetching a page of results starting at index: {}", start_at);
+            } else {
+                break;
+            }
+        }
+
+        if let Some(limit) = options.limit {
+            results.truncate(limit);
+        }
+        for result in &mut results {
+            super::take_epic(&all_fields, &mut result.fields);
+        }
+
+        Ok(results)
+    }
+
+    async fn search_issues_single_page(
+        &self,
+        search_jql: &str,
+        fields: &str,
+        start_at: usize,
+        max_results: Option<usize>,
+    ) -> Result<IssueSearchResponse> {
+        let mut query = vec![
+            ("jql", search_jql.to_owned()),
+            ("startAt", start_at.to_string()),
+            ("fields", fields.to_owned()),
+        ];
+        if let Some(max_results) = max_results {
+            query.push(("maxResults", max_results.to_string()));
+        }
+
+        let request = self.request("/search", Method::GET).query(&query);
+        let response = request.send().await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let results = response.json::<IssueSearchResponse>().await?;
+                Ok(results)
+            }
+            _ => Err(Error::from_response(response).await),
         }
-        code => Err(Box::new(ApiError::new(&format!(
-            "Got a {} when attempting to list issues assigned to me, {}",
-            code,
-            response.text().await?
-        )))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectProfile;
+    use crate::ApiConfig;
+
+    fn client(profile: ProjectProfile) -> JiraClient {
+        JiraClient::new(ApiConfig {
+            email: "".to_owned(),
+            token: "".to_owned(),
+            subdomain: "abcd".to_owned(),
+            project: "ABCD".to_owned(),
+            base_url: None,
+            profile,
+        })
+    }
+
+    #[test]
+    fn test_scope_defaults_to_project() {
+        assert_eq!(client(Default::default()).scope_jql(), "project = \"ABCD\"");
+    }
+
+    #[test]
+    fn test_scope_with_profile() {
+        let profile = ProjectProfile {
+            components: vec!["iOS".to_owned(), "Android".to_owned()],
+            filter: Some("labels = mobile".to_owned()),
+        };
+        assert_eq!(
+            client(profile).scope_jql(),
+            "project = \"ABCD\" AND component in (\"iOS\", \"Android\") AND (labels = mobile)"
+        );
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let profile = ProjectProfile {
+            components: vec!["iOS".to_owned()],
+            filter: None,
+        };
+        assert_eq!(
+            client(profile)
+                .expand_jql("assignee = {me} AND component in ({component}) AND {scope}")
+                .unwrap(),
+            "assignee = currentUser() AND component in (\"iOS\") AND project = \"ABCD\" AND component in (\"iOS\")"
+        );
+    }
+
+    #[test]
+    fn test_order_jql() {
+        assert_eq!(order_jql("project = ABC", None), "project = ABC");
+        assert_eq!(
+            order_jql("project = ABC", Some("updated DESC")),
+            "project = ABC ORDER BY updated DESC"
+        );
+        assert_eq!(
+            order_jql("project = ABC order  by rank", Some("updated DESC")),
+            "project = ABC ORDER BY updated DESC"
+        );
+        // Not when it's part of a value, or a word that starts the same
+        assert_eq!(
+            order_jql(
+                r#"summary ~ "sort \" order by" AND reorder_by = 1"#,
+                Some("key")
+            ),
+            r#"summary ~ "sort \" order by" AND reorder_by = 1 ORDER BY key"#
+        );
+    }
+
+    #[test]
+    fn test_search_fields() {
+        let fields: model::Fields = serde_json::from_str(
+            r#"[{"id": "customfield_10014", "name": "Epic Link", "custom": true, "schema": {"type": "any", "custom": "com.pyxis.greenhopper.jira:gh-epic-link"}},
+                {"id": "customfield_10016", "name": "Story Points", "custom": true, "schema": {"type": "number"}}]"#,
+        )
+        .unwrap();
+        let extra = ["story points", "customfield_10099", "labels"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        let requested = search_fields(&fields, &extra);
+        let requested = requested.split(',').collect::<Vec<_>>();
+
+        assert_eq!(requested.len(), DEFAULT_FIELDS.len() + 3);
+        assert!(requested.starts_with(&DEFAULT_FIELDS));
+        assert_eq!(
+            requested[DEFAULT_FIELDS.len()..],
+            [
+                "customfield_10014",
+                "customfield_10016",
+                "customfield_10099"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_component_without_profile() {
+        assert!(client(Default::default())
+            .expand_jql("component in ({component})")
+            .is_err());
     }
 }
@@ -0,0 +1,476 @@
+//! Ordering and grouping of issue lists, for `--sort` and `--group-by`.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use super::model::{IssueEpic, IssueSearchResult, IssueStatus, StatusCategoryKey};
+
+/// A field that issue lists can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Key,
+    Type,
+    Status,
+    Priority,
+    Assignee,
+    Summary,
+    Created,
+    Updated,
+    Due,
+    Epic,
+}
+
+const SORT_FIELDS: [(&str, SortField); 10] = [
+    ("key", SortField::Key),
+    ("type", SortField::Type),
+    ("status", SortField::Status),
+    ("priority", SortField::Priority),
+    ("assignee", SortField::Assignee),
+    ("summary", SortField::Summary),
+    ("created", SortField::Created),
+    ("updated", SortField::Updated),
+    ("due", SortField::Due),
+    ("epic", SortField::Epic),
+];
+
+/// One key of a sort, e.g. `-updated` for the most recently updated issues first.
+/// Ascending order puts statuses in workflow order (to do, in progress, done) and
+/// priorities from most to least urgent. Issues without a value always come last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, descending) = match s.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (s.strip_prefix('+').unwrap_or(s), false),
+        };
+
+        match SORT_FIELDS
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+        {
+            Some((_, field)) => Ok(SortKey {
+                field: *field,
+                descending,
+            }),
+            None => Err(format!(
+                "unknown sort field '{}', expected one of: {}",
+                name,
+                SORT_FIELDS
+                    .iter()
+                    .map(|(field, _)| *field)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// Parse comma separated sort keys, e.g. `priority,-updated,status`.
+pub fn parse_sort(spec: &str) -> Result<Vec<SortKey>, String> {
+    spec.split(',')
+        .filter(|key| !key.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// How issue lists are sorted unless asked otherwise: by type, then status, then key.
+pub fn default_sort() -> Vec<SortKey> {
+    [SortField::Type, SortField::Status, SortField::Key]
+        .iter()
+        .map(|field| SortKey {
+            field: *field,
+            descending: false,
+        })
+        .collect()
+}
+
+/// Order statuses by category (to do, in progress, done), then by name.
+pub fn status_order(status: Option<&IssueStatus>) -> (StatusCategoryKey, String) {
+    match status {
+        Some(status) => (status.status_category.key, status.name.to_owned()),
+        None => (StatusCategoryKey::Undefined, String::new()),
+    }
+}
+
+/// Order issue keys by project, then number, so `ABC-2` comes before `ABC-10`.
+fn key_order(key: &str) -> (String, u64) {
+    match key.rfind('-') {
+        Some(i) => match key[i + 1..].parse() {
+            Ok(number) => (key[..i].to_owned(), number),
+            Err(_) => (key.to_owned(), 0),
+        },
+        None => (key.to_owned(), 0),
+    }
+}
+
+/// Rank the usual priority names from most to least urgent; others come after them.
+fn priority_order(name: &str) -> (u8, String) {
+    let name = name.to_lowercase();
+    let rank = match name.as_str() {
+        "highest" | "blocker" => 0,
+        "critical" => 1,
+        "high" | "major" => 2,
+        "medium" | "normal" => 3,
+        "low" | "minor" => 4,
+        "lowest" | "trivial" => 5,
+        _ => 6,
+    };
+    (rank, name)
+}
+
+/// The key of the epic an issue belongs to: its epic link, or (in team-managed projects)
+/// its parent, if that's an epic.
+fn epic_key(issue: &IssueSearchResult) -> Option<&str> {
+    match &issue.fields.epic {
+        Some(IssueEpic::Key(key)) => Some(key),
+        Some(IssueEpic::Full(epic)) => Some(&epic.key),
+        None => issue
+            .fields
+            .parent
+            .as_ref()
+            .filter(|parent| {
+                parent
+                    .fields
+                    .as_ref()
+                    .map_or(false, |f| f.issuetype.name == "Epic")
+            })
+            .map(|parent| parent.key.as_str()),
+    }
+}
+
+// Compare optional values, putting missing ones last whichever way we're sorting.
+fn compare_present<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl SortKey {
+    fn compare(&self, a: &IssueSearchResult, b: &IssueSearchResult) -> Ordering {
+        fn by<T: Ord>(
+            key: &SortKey,
+            a: &IssueSearchResult,
+            b: &IssueSearchResult,
+            value: impl Fn(&IssueSearchResult) -> Option<T>,
+        ) -> Ordering {
+            compare_present(value(a), value(b), key.descending)
+        }
+
+        match self.field {
+            SortField::Key => by(self, a, b, |i| Some(key_order(&i.key))),
+            SortField::Type => by(self, a, b, |i| Some(i.fields.issuetype.name.to_lowercase())),
+            SortField::Status => by(self, a, b, |i| {
+                i.fields.status.as_ref().map(|s| status_order(Some(s)))
+            }),
+            SortField::Priority => by(self, a, b, |i| {
+                i.fields.priority.as_ref().map(|p| priority_order(&p.name))
+            }),
+            SortField::Assignee => by(self, a, b, |i| {
+                i.fields
+                    .assignee
+                    .as_ref()
+                    .map(|a| a.display_name.to_lowercase())
+            }),
+            SortField::Summary => by(self, a, b, |i| Some(i.fields.summary.to_lowercase())),
+            SortField::Created => by(self, a, b, |i| i.fields.created),
+            SortField::Updated => by(self, a, b, |i| i.fields.updated),
+            SortField::Due => by(self, a, b, |i| i.fields.duedate),
+            SortField::Epic => by(self, a, b, |i| epic_key(i).map(key_order)),
+        }
+    }
+}
+
+/// Sort issues by each key in turn. The sort is stable, so issues that tie on every key
+/// keep their order (usually the order Jira returned them in).
+pub fn sort_issues(issues: &mut [IssueSearchResult], keys: &[SortKey]) {
+    issues.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| key.compare(a, b))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Move issues to just after their parent, when the parent is in the list too. Children keep
+/// their order relative to each other, and nothing is dropped, even if parents are missing.
+pub fn nest_subtasks(issues: Vec<IssueSearchResult>) -> Vec<IssueSearchResult> {
+    let keys = issues
+        .iter()
+        .map(|issue| issue.key.to_owned())
+        .collect::<HashSet<_>>();
+    let parent_key = |issue: &IssueSearchResult| {
+        issue
+            .fields
+            .parent
+            .as_ref()
+            .map(|parent| parent.key.to_owned())
+            .filter(|parent| keys.contains(parent) && *parent != issue.key)
+    };
+
+    let mut roots = Vec::new();
+    let mut children: HashMap<String, Vec<(usize, IssueSearchResult)>> = HashMap::new();
+    for (i, issue) in issues.into_iter().enumerate() {
+        match parent_key(&issue) {
+            Some(parent) => children.entry(parent).or_default().push((i, issue)),
+            None => roots.push(issue),
+        }
+    }
+
+    fn push_with_children(
+        issue: IssueSearchResult,
+        children: &mut HashMap<String, Vec<(usize, IssueSearchResult)>>,
+        nested: &mut Vec<IssueSearchResult>,
+    ) {
+        let key = issue.key.to_owned();
+        nested.push(issue);
+        for (_, child) in children.remove(&key).unwrap_or_default() {
+            push_with_children(child, children, nested);
+        }
+    }
+
+    let mut nested = Vec::new();
+    for issue in roots {
+        push_with_children(issue, &mut children, &mut nested);
+    }
+
+    // Only a cycle of parents can leave issues behind; keep them, in their original order
+    let mut rest = children.into_values().flatten().collect::<Vec<_>>();
+    rest.sort_by_key(|(i, _)| *i);
+    nested.extend(rest.into_iter().map(|(_, issue)| issue));
+    nested
+}
+
+/// Sort issues by `keys`, then nest subtasks under their parents. An empty list of keys
+/// leaves the issues as they are.
+pub fn arrange(mut issues: Vec<IssueSearchResult>, keys: &[SortKey]) -> Vec<IssueSearchResult> {
+    if keys.is_empty() {
+        return issues;
+    }
+    sort_issues(&mut issues, keys);
+    nest_subtasks(issues)
+}
+
+/// What to split an issue list into sections by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Status,
+    Assignee,
+    Epic,
+    Component,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "status" => Ok(GroupBy::Status),
+            "assignee" => Ok(GroupBy::Assignee),
+            "epic" => Ok(GroupBy::Epic),
+            "component" => Ok(GroupBy::Component),
+            _ => Err(format!(
+                "unknown group '{}', expected one of: status, assignee, epic, component",
+                s
+            )),
+        }
+    }
+}
+
+// Sections are ordered by this, with issues that don't have a value last.
+type GroupOrder = (bool, (StatusCategoryKey, String), (String, u64));
+
+fn group_of(issue: &IssueSearchResult, by: GroupBy) -> (GroupOrder, String) {
+    let fields = &issue.fields;
+    let none = |label: &str| {
+        (
+            (true, Default::default(), Default::default()),
+            label.to_owned(),
+        )
+    };
+    let named = |name: &str| {
+        (
+            (false, Default::default(), (name.to_lowercase(), 0)),
+            name.to_owned(),
+        )
+    };
+
+    match by {
+        GroupBy::Status => match &fields.status {
+            Some(status) => (
+                (false, status_order(Some(status)), Default::default()),
+                status.name.to_owned(),
+            ),
+            None => none("No status"),
+        },
+        GroupBy::Assignee => match &fields.assignee {
+            Some(assignee) => named(&assignee.display_name),
+            None => none("Unassigned"),
+        },
+        GroupBy::Epic => match epic_key(issue) {
+            Some(key) => {
+                let label = match &fields.epic {
+                    Some(IssueEpic::Full(epic)) => format!("{}: {}", key, epic.fields.summary),
+                    _ => key.to_owned(),
+                };
+                ((false, Default::default(), key_order(key)), label)
+            }
+            None => none("No epic"),
+        },
+        // Issues in several components go under the first one, alphabetically
+        GroupBy::Component => match fields
+            .components
+            .iter()
+            .flatten()
+            .map(|c| c.name.as_str())
+            .min_by_key(|name| name.to_lowercase())
+        {
+            Some(name) => named(name),
+            None => none("No component"),
+        },
+    }
+}
+
+/// Split issues into labelled sections, keeping their order within each section.
+pub fn group_issues(
+    issues: Vec<IssueSearchResult>,
+    by: GroupBy,
+) -> Vec<(String, Vec<IssueSearchResult>)> {
+    let mut groups: Vec<(GroupOrder, String, Vec<IssueSearchResult>)> = Vec::new();
+    for issue in issues {
+        let (order, label) = group_of(&issue, by);
+        match groups.iter_mut().find(|group| group.1 == label) {
+            Some(group) => group.2.push(issue),
+            None => groups.push((order, label, vec![issue])),
+        }
+    }
+
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    groups
+        .into_iter()
+        .map(|(_, label, issues)| (label, issues))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(key: &str, fields: &str) -> IssueSearchResult {
+        serde_json::from_str(&format!(
+            r#"{{"id": "1", "key": "{}", "fields": {{"summary": "{}", "issuetype": {{"name": "Task"}}{}}}}}"#,
+            key,
+            key,
+            if fields.is_empty() {
+                String::new()
+            } else {
+                format!(", {}", fields)
+            }
+        ))
+        .unwrap()
+    }
+
+    fn keys(issues: &[IssueSearchResult]) -> Vec<&str> {
+        issues.iter().map(|issue| issue.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        assert_eq!(
+            parse_sort("priority, -updated").unwrap(),
+            vec![
+                SortKey {
+                    field: SortField::Priority,
+                    descending: false
+                },
+                SortKey {
+                    field: SortField::Updated,
+                    descending: true
+                },
+            ]
+        );
+        assert!(parse_sort("priority,-size").is_err());
+    }
+
+    #[test]
+    fn test_sort_by_several_keys() {
+        let mut issues = vec![
+            issue(
+                "ABC-10",
+                r#""priority": {"name": "Low"}, "updated": "2020-06-02T10:00:00.000+0000""#,
+            ),
+            issue("ABC-2", r#""priority": {"name": "Highest"}"#),
+            issue(
+                "ABC-3",
+                r#""priority": {"name": "Low"}, "updated": "2020-06-03T10:00:00.000+0000""#,
+            ),
+            issue("ABC-4", ""),
+            issue(
+                "ABC-1",
+                r#""priority": {"name": "Low"}, "updated": "2020-06-02T10:00:00.000+0000""#,
+            ),
+        ];
+        sort_issues(&mut issues, &parse_sort("priority,-updated").unwrap());
+        // Ties keep their order, and issues without a priority come last
+        assert_eq!(
+            keys(&issues),
+            vec!["ABC-2", "ABC-3", "ABC-10", "ABC-1", "ABC-4"]
+        );
+
+        sort_issues(&mut issues, &parse_sort("key").unwrap());
+        assert_eq!(
+            keys(&issues),
+            vec!["ABC-1", "ABC-2", "ABC-3", "ABC-4", "ABC-10"]
+        );
+    }
+
+    #[test]
+    fn test_nest_subtasks() {
+        let issues = vec![
+            issue("ABC-3", r#""parent": {"key": "ABC-1"}"#),
+            issue("ABC-2", ""),
+            // A parent that isn't in the list, and one without fields
+            issue("ABC-5", r#""parent": {"key": "XYZ-9", "fields": null}"#),
+            issue("ABC-1", ""),
+            issue("ABC-4", r#""parent": {"key": "ABC-1"}"#),
+        ];
+        assert_eq!(
+            keys(&nest_subtasks(issues)),
+            vec!["ABC-2", "ABC-5", "ABC-1", "ABC-3", "ABC-4"]
+        );
+    }
+
+    #[test]
+    fn test_group_issues() {
+        let issues = vec![
+            issue("ABC-1", r#""assignee": {"displayName": "Zoe"}"#),
+            issue("ABC-2", ""),
+            issue("ABC-3", r#""assignee": {"displayName": "amy"}"#),
+            issue("ABC-4", r#""assignee": {"displayName": "Zoe"}"#),
+        ];
+        let groups = group_issues(issues, GroupBy::Assignee)
+            .iter()
+            .map(|(label, issues)| (label.to_owned(), keys(issues).join(" ")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                ("amy".to_owned(), "ABC-3".to_owned()),
+                ("Zoe".to_owned(), "ABC-1 ABC-4".to_owned()),
+                ("Unassigned".to_owned(), "ABC-2".to_owned()),
+            ]
+        );
+    }
+}
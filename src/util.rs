@@ -1,6 +1,13 @@
 use super::ApiConfig;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 use regex::Regex;
 
+// Jira's defaults for time tracking: an 8 hour day, and a 5 day week
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 8 * HOUR;
+const WEEK: u64 = 5 * DAY;
+
 /// Convert an issue key to it's canonical form.
 pub fn issue_lossy_to_issue_key(issue_lossy: &str, config: &ApiConfig) -> Option<String> {
     let issue_pattern = Regex::new(r"^[A-Z]+\-\d+$").unwrap();
@@ -15,6 +22,85 @@ pub fn issue_lossy_to_issue_key(issue_lossy: &str, config: &ApiConfig) -> Option
     }
 }
 
+/// Parse a Jira duration like `1w 2d 3h 4m`, `2h30m` or `1.5h` into seconds. Days are
+/// 8 hours and weeks 5 days, as in Jira.
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let part = Regex::new(r"^(?i)(\d+(?:\.\d+)?)\s*([wdhm])\s*").unwrap();
+
+    let mut rest = duration.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let captures = part.captures(rest)?;
+        let value = captures[1].parse::<f64>().ok()?;
+        let unit = match captures[2].to_ascii_lowercase().as_str() {
+            "w" => WEEK,
+            "d" => DAY,
+            "h" => HOUR,
+            _ => MINUTE,
+        };
+        seconds += value * unit as f64;
+        rest = &rest[captures[0].len()..];
+    }
+    Some(seconds.round() as u64)
+}
+
+/// Format seconds the way Jira does, e.g. `1w 2d 3h 4m`.
+pub fn format_duration(seconds: u64) -> String {
+    let mut parts = Vec::new();
+    let mut rest = seconds;
+    for (unit, suffix) in &[(WEEK, "w"), (DAY, "d"), (HOUR, "h"), (MINUTE, "m")] {
+        if rest >= *unit {
+            parts.push(format!("{}{}", rest / unit, suffix));
+            rest %= unit;
+        }
+    }
+    if parts.is_empty() {
+        "0m".to_owned()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Parse a local date and time: `2020-06-01 09:30`, `2020-06-01` (the start of the day),
+/// `09:30` (today), or RFC 3339.
+pub fn parse_datetime(value: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.with_timezone(&Local));
+    }
+
+    let naive = ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()))
+        })
+        .or_else(|| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .ok()
+                .map(|time| now.date_naive().and_time(time))
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// Format a date and time the way Jira expects it in requests.
+pub fn jira_datetime(datetime: &DateTime<Local>) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string()
+}
+
+/// Format one of Jira's timestamps in local time, down to the minute.
+pub fn local_datetime(datetime: &DateTime<FixedOffset>) -> String {
+    datetime
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -26,6 +112,8 @@ mod tests {
             token: "".to_owned(),
             subdomain: "abcd".to_owned(),
             project: "ABCD".to_owned(),
+            base_url: None,
+            profile: Default::default(),
         }
     }
 
@@ -49,4 +137,50 @@ mod tests {
     fn test_issue_key_nonsense() {
         assert_eq!(issue_lossy_to_issue_key("alsdkflksaj", &config()), None)
     }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2h30m"), Some(2 * HOUR + 30 * MINUTE));
+        assert_eq!(
+            parse_duration("1w 2d 3h 4m"),
+            Some(WEEK + 2 * DAY + 3 * HOUR + 4 * MINUTE)
+        );
+        assert_eq!(parse_duration("1.5h"), Some(90 * MINUTE));
+        assert_eq!(parse_duration(" 45M "), Some(45 * MINUTE));
+        assert_eq!(parse_duration("1d"), Some(8 * HOUR));
+    }
+
+    #[test]
+    fn test_parse_duration_nonsense() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("2 hours"), None);
+        assert_eq!(parse_duration("h"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(9000), "2h 30m");
+        assert_eq!(format_duration(WEEK + DAY + 4 * MINUTE), "1w 1d 4m");
+        assert_eq!(format_duration(0), "0m");
+        assert_eq!(
+            parse_duration(&format_duration(3 * DAY + HOUR)),
+            Some(3 * DAY + HOUR)
+        );
+    }
+
+    #[test]
+    fn test_parse_datetime() {
+        let now = Local.with_ymd_and_hms(2020, 6, 3, 15, 0, 0).unwrap();
+        let parse = |value| {
+            parse_datetime(value, now).map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+        };
+        assert_eq!(
+            parse("2020-06-01 09:30").as_deref(),
+            Some("2020-06-01 09:30")
+        );
+        assert_eq!(parse("2020-06-01").as_deref(), Some("2020-06-01 00:00"));
+        assert_eq!(parse("09:30").as_deref(), Some("2020-06-03 09:30"));
+        assert_eq!(parse("yesterday"), None);
+    }
 }